@@ -0,0 +1,26 @@
+//! Arbitrary bytes through the whole parsing surface.
+//!
+//! The input is cut into `pk || ct || sk`, using the exact sizes while there
+//! is enough data, so both length errors and structurally valid blobs are
+//! reached. Whatever the bytes, the only acceptable failure is a `MirError`;
+//! any panic is a bug.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mirletis::{decaps_from_bytes, MirCiphertext, MirPubkey, CIPHERTEXT_BYTES, PUBKEY_BYTES};
+
+fuzz_target!(|data: &[u8]| {
+    let (pk_bytes, rest) = data.split_at(data.len().min(PUBKEY_BYTES));
+    let (ct_bytes, sk_bytes) = rest.split_at(rest.len().min(CIPHERTEXT_BYTES));
+
+    // Parsing alone must never panic, and what parses must re-encode verbatim
+    if let Ok(pk) = MirPubkey::from_bytes(pk_bytes) {
+        assert_eq!(pk.to_bytes()[..], *pk_bytes);
+    }
+    if let Ok(ct) = MirCiphertext::from_bytes(ct_bytes) {
+        assert_eq!(ct.to_bytes()[..], *ct_bytes);
+    }
+
+    let _ = decaps_from_bytes(pk_bytes, ct_bytes, sk_bytes);
+});
@@ -0,0 +1,333 @@
+/*
+ * Deterministic CBOR (RFC 8949, Section 4.2.1) for MirPubkey / MirCiphertext.
+ *
+ * Each struct is a map with small unsigned-integer keys in ascending order:
+ *   MirPubkey     { 0: seed (bstr), 1: b (bstr) }
+ *   MirCiphertext { 0: u (bstr), 1: mask (bstr), 2: cnt (uint) }
+ *
+ * Heads always use the shortest form, so two encodings of the same value are
+ * byte-identical. The decoder accepts the canonical form only.
+ *
+ * The encoder writes into a fixed buffer of the largest encoding, so
+ * `write_cbor` needs no allocator; `to_cbor` copies the result into a `Vec`.
+ */
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::{MirCiphertext, MirError, MirPubkey, K, N, SEED_LEN};
+
+const MAJOR_UINT: u8 = 0;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_MAP: u8 = 5;
+
+/* === [1. Encoder] === */
+
+/// Size of the shortest head for `val`.
+const fn head_len(val: u64) -> usize {
+    if val < 24 {
+        1
+    } else if val <= 0xFF {
+        2
+    } else if val <= 0xFFFF {
+        3
+    } else if val <= 0xFFFF_FFFF {
+        5
+    } else {
+        9
+    }
+}
+
+/// Size of a `key: bstr` map entry holding `len` bytes.
+const fn entry_len(key: u64, len: usize) -> usize {
+    head_len(key) + head_len(len as u64) + len
+}
+
+/// Appends to a buffer sized by the caller for the largest encoding.
+struct Writer<'a> {
+    out: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn new(out: &'a mut [u8]) -> Self {
+        Writer { out, pos: 0 }
+    }
+
+    fn put(&mut self, data: &[u8]) {
+        self.out[self.pos..self.pos + data.len()].copy_from_slice(data);
+        self.pos += data.len();
+    }
+
+    fn head(&mut self, major: u8, val: u64) {
+        let m = major << 5;
+        if val < 24 {
+            self.put(&[m | val as u8]);
+        } else if val <= 0xFF {
+            self.put(&[m | 24, val as u8]);
+        } else if val <= 0xFFFF {
+            self.put(&[m | 25]);
+            self.put(&(val as u16).to_be_bytes());
+        } else if val <= 0xFFFF_FFFF {
+            self.put(&[m | 26]);
+            self.put(&(val as u32).to_be_bytes());
+        } else {
+            self.put(&[m | 27]);
+            self.put(&val.to_be_bytes());
+        }
+    }
+
+    fn bytes(&mut self, key: u64, data: &[u8]) {
+        self.head(MAJOR_UINT, key);
+        self.head(MAJOR_BYTES, data.len() as u64);
+        self.put(data);
+    }
+
+    fn finish(self) -> &'a [u8] {
+        &self.out[..self.pos]
+    }
+}
+
+/* === [2. Decoder] === */
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], MirError> {
+        let end = self.pos.checked_add(len).ok_or(MirError::BadLength)?;
+        let out = self.data.get(self.pos..end).ok_or(MirError::BadLength)?;
+        self.pos = end;
+        Ok(out)
+    }
+
+    /// Reads a head of the expected major type, rejecting non-shortest forms.
+    fn head(&mut self, major: u8) -> Result<u64, MirError> {
+        let ib = self.take(1)?[0];
+        if ib >> 5 != major {
+            return Err(MirError::Malformed);
+        }
+        let (val, min) = match ib & 0x1F {
+            v @ 0..=23 => return Ok(v as u64),
+            24 => (self.take(1)?[0] as u64, 24),
+            25 => {
+                let b = self.take(2)?;
+                (u16::from_be_bytes([b[0], b[1]]) as u64, 0x100)
+            }
+            26 => {
+                let b = self.take(4)?;
+                (u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as u64, 0x1_0000)
+            }
+            27 => {
+                let b = self.take(8)?;
+                let mut a = [0u8; 8];
+                a.copy_from_slice(b);
+                (u64::from_be_bytes(a), 0x1_0000_0000)
+            }
+            _ => return Err(MirError::Malformed),
+        };
+        if val < min {
+            return Err(MirError::Malformed);
+        }
+        Ok(val)
+    }
+
+    fn key(&mut self, expected: u64) -> Result<(), MirError> {
+        if self.head(MAJOR_UINT)? != expected {
+            return Err(MirError::Malformed);
+        }
+        Ok(())
+    }
+
+    fn bytes_into(&mut self, out: &mut [u8]) -> Result<(), MirError> {
+        let len = self.head(MAJOR_BYTES)?;
+        if len != out.len() as u64 {
+            return Err(MirError::BadLength);
+        }
+        out.copy_from_slice(self.take(out.len())?);
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<(), MirError> {
+        if self.pos != self.data.len() {
+            return Err(MirError::Malformed);
+        }
+        Ok(())
+    }
+}
+
+/* === [3. Public API] === */
+
+impl MirPubkey {
+    /// Size of the CBOR encoding (the same for every key).
+    pub const CBOR_MAX_LEN: usize = head_len(2) + entry_len(0, SEED_LEN) + entry_len(1, K * N);
+
+    /// Encodes the public key as deterministic CBOR.
+    #[cfg(feature = "alloc")]
+    pub fn to_cbor(&self) -> Vec<u8> {
+        self.write_cbor(&mut [0u8; Self::CBOR_MAX_LEN]).to_vec()
+    }
+
+    /// `to_cbor` into a caller-owned buffer, returning the encoding in it.
+    pub fn write_cbor<'a>(&self, out: &'a mut [u8; Self::CBOR_MAX_LEN]) -> &'a [u8] {
+        let mut w = Writer::new(out);
+        w.head(MAJOR_MAP, 2);
+        w.bytes(0, &self.seed);
+        w.bytes(1, &self.b);
+        w.finish()
+    }
+
+    /// Decodes a public key from deterministic CBOR.
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, MirError> {
+        let mut r = Reader::new(bytes);
+        if r.head(MAJOR_MAP)? != 2 {
+            return Err(MirError::Malformed);
+        }
+
+        let mut pk = MirPubkey::default();
+        r.key(0)?;
+        r.bytes_into(&mut pk.seed)?;
+        r.key(1)?;
+        r.bytes_into(&mut pk.b)?;
+        r.finish()?;
+
+        Ok(pk)
+    }
+}
+
+impl MirCiphertext {
+    /// Upper bound on the CBOR encoding, which varies with the head of `cnt`.
+    pub const CBOR_MAX_LEN: usize =
+        head_len(3) + entry_len(0, K * N) + entry_len(1, N / 8) + head_len(2) + head_len(u16::MAX as u64);
+
+    /// Encodes the ciphertext as deterministic CBOR.
+    #[cfg(feature = "alloc")]
+    pub fn to_cbor(&self) -> Vec<u8> {
+        self.write_cbor(&mut [0u8; Self::CBOR_MAX_LEN]).to_vec()
+    }
+
+    /// `to_cbor` into a caller-owned buffer, returning the encoding in it.
+    pub fn write_cbor<'a>(&self, out: &'a mut [u8; Self::CBOR_MAX_LEN]) -> &'a [u8] {
+        let mut w = Writer::new(out);
+        w.head(MAJOR_MAP, 3);
+        w.bytes(0, &self.u);
+        w.bytes(1, &self.mask);
+        w.head(MAJOR_UINT, 2);
+        w.head(MAJOR_UINT, self.cnt as u64);
+        w.finish()
+    }
+
+    /// Decodes a ciphertext from deterministic CBOR.
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, MirError> {
+        let mut r = Reader::new(bytes);
+        if r.head(MAJOR_MAP)? != 3 {
+            return Err(MirError::Malformed);
+        }
+
+        let mut ct = MirCiphertext {
+            u: [0u8; K * N],
+            mask: [0u8; N / 8],
+            cnt: 0,
+        };
+        r.key(0)?;
+        r.bytes_into(&mut ct.u)?;
+        r.key(1)?;
+        r.bytes_into(&mut ct.mask)?;
+        r.key(2)?;
+        let cnt = r.head(MAJOR_UINT)?;
+        if cnt > N as u64 {
+            return Err(MirError::OutOfRange);
+        }
+        ct.cnt = cnt as u16;
+        r.finish()?;
+
+        Ok(ct)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decaps, encaps, keygen};
+
+    #[test]
+    fn test_write_cbor() {
+        let (pk, vault) = keygen();
+        let mut buf = [0u8; MirPubkey::CBOR_MAX_LEN];
+        let enc = pk.write_cbor(&mut buf);
+        assert_eq!(enc.len(), MirPubkey::CBOR_MAX_LEN);
+        assert!(MirPubkey::from_cbor(enc).unwrap().ct_eq(&pk));
+
+        let (mut ct, key) = encaps(&pk);
+        let mut buf = [0u8; MirCiphertext::CBOR_MAX_LEN];
+        let ct2 = MirCiphertext::from_cbor(ct.write_cbor(&mut buf)).unwrap();
+        assert_eq!(decaps(&ct2, &vault).key, key.key);
+
+        // Only a `cnt` beyond one byte fills the whole buffer
+        ct.cnt = u16::MAX;
+        assert_eq!(ct.write_cbor(&mut buf).len(), MirCiphertext::CBOR_MAX_LEN);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_cbor_roundtrip_is_deterministic() {
+        let (pk, vault) = keygen();
+        let enc = pk.to_cbor();
+        assert_eq!(enc, pk.clone().to_cbor());
+        let pk2 = MirPubkey::from_cbor(&enc).unwrap();
+        assert_eq!(pk2.seed, pk.seed);
+        assert_eq!(pk2.b[..], pk.b[..]);
+
+        let (ct, key) = encaps(&pk);
+        let enc = ct.to_cbor();
+        let ct2 = MirCiphertext::from_cbor(&enc).unwrap();
+        assert_eq!(ct2.to_cbor(), enc);
+        assert_eq!(decaps(&ct2, &vault).key, key.key);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_cbor_cnt_is_shortest_uint() {
+        let (pk, _) = keygen();
+        let (mut ct, _) = encaps(&pk);
+
+        ct.cnt = 23;
+        assert_eq!(ct.to_cbor().last(), Some(&23));
+        ct.cnt = 200;
+        assert_eq!(ct.to_cbor()[ct.to_cbor().len() - 2..], [0x18, 200]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_cbor_rejects_malformed() {
+        let (pk, _) = keygen();
+        let enc = pk.to_cbor();
+
+        assert_eq!(MirPubkey::from_cbor(&enc[..enc.len() - 1]).err(), Some(MirError::BadLength));
+
+        let mut trailing = enc.clone();
+        trailing.push(0);
+        assert_eq!(MirPubkey::from_cbor(&trailing).err(), Some(MirError::Malformed));
+
+        let mut wrong_key = enc.clone();
+        wrong_key[1] = 0x05;
+        assert_eq!(MirPubkey::from_cbor(&wrong_key).err(), Some(MirError::Malformed));
+
+        // Non-canonical: the 32-byte seed length encoded with a 2-byte head.
+        let mut long_head = Vec::new();
+        long_head.extend_from_slice(&[0xA2, 0x00, 0x59, 0x00, 0x20]);
+        long_head.extend_from_slice(&enc[4..]);
+        assert_eq!(MirPubkey::from_cbor(&long_head).err(), Some(MirError::Malformed));
+
+        // Well-formed CBOR, but a count no mask can reach
+        let (mut ct, _) = encaps(&pk);
+        ct.cnt = N as u16 + 1;
+        assert_eq!(MirCiphertext::from_cbor(&ct.to_cbor()).err(), Some(MirError::OutOfRange));
+    }
+}
@@ -0,0 +1,432 @@
+/*
+ * Const-generic parameter sets: the same KEM over any rank K.
+ *
+ * MirPubkeyG<K> / MirCiphertextG<K> / MirSecretVaultG<K> run the exact
+ * construction of the crate-wide types (same hashes, domain tags, safe zone
+ * and byte layouts, with every `K * N` taken from the type parameter), so a
+ * "light" and a "heavy" set can be compiled side by side. For K equal to
+ * the crate's `K` the bytes match `MirPubkey` / `MirCiphertext` exactly and
+ * `From` converts losslessly.
+ *
+ * The fixed types are not aliases of these: their flat `[u8; K * N]` fields
+ * are public API, and stable Rust cannot spell `[u8; K * N]` for a generic K,
+ * hence the `[[u8; N]; K]` blocks here. Everything that does not depend
+ * on the field types is shared rather than copied: the byte layouts live
+ * here once (`write_pubkey` / `read_pubkey` and the ciphertext pair, which
+ * `MirPubkey` / `MirCiphertext` serialize through), keygen runs the crate's
+ * own draw and seed drivers (`keygen_fresh_with` / `keygen_seeded_with`) and
+ * weak-secret floor, and encaps/decaps finish in `encaps_finish` /
+ * `decaps_core`. Everything stays on the stack,
+ * including the `K * K * N` matrix (see `Rank`), so no allocator is needed.
+ */
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use rand::{CryptoRng, RngCore};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::{
+    check_cnt, check_header, ct, decaps_core, encaps_finish, gen_secret_from_seed, keygen_fresh_with, keygen_seeded_with,
+    min_secret_weight, poly, secret_weight, write_header, DefaultRng, KeygenVault, MirCiphertext, MirError, MirPubkey,
+    MirSharedKey, ScratchGuard, HEADER_BYTES, N, SEED_LEN, SHARED_LEN,
+};
+
+/* === [1. Rank Check] === */
+
+/// Compile-time guard, evaluated once per rank in use: the noise bound for
+/// `K` blocks must still fit the safe zone (`K <= 10` for `q = 2^13`).
+///
+/// The bound also caps the stack: keygen and encaps expand the whole
+/// `[[[i16; N]; K]; K]` matrix locally, `2 * K * K * N` bytes, which is
+/// 12.8 KB at `K = 5` and about 51 KB at `K = 10`. Size task stacks for
+/// the largest rank in use.
+struct Rank<const K: usize>;
+
+impl<const K: usize> Rank<K> {
+    const VALID: () = assert!(
+        K >= 1 && ct::ZONE_THRESHOLD + ct::zone_max_drift(K) <= 32,
+        "rank out of range for the safe zone"
+    );
+}
+
+/* === [2. Data Structures] === */
+
+/// Public key of rank `K`: matrix seed and `b` as `K` blocks of `N`.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct MirPubkeyG<const K: usize> {
+    pub seed: [u8; SEED_LEN],
+    pub b: [[u8; N]; K],
+}
+
+impl<const K: usize> Default for MirPubkeyG<K> {
+    fn default() -> Self {
+        Self {
+            seed: [0u8; SEED_LEN],
+            b: [[0u8; N]; K],
+        }
+    }
+}
+
+/// Ciphertext of rank `K`: `u` as `K` blocks of `N`, the safe-zone mask and count.
+#[derive(Clone, Copy)]
+pub struct MirCiphertextG<const K: usize> {
+    pub u: [[u8; N]; K],
+    pub mask: [u8; N / 8],
+    pub cnt: u16,
+}
+
+impl<const K: usize> Default for MirCiphertextG<K> {
+    fn default() -> Self {
+        Self {
+            u: [[0u8; N]; K],
+            mask: [0u8; N / 8],
+            cnt: 0,
+        }
+    }
+}
+
+/// Secret vault of rank `K`, with the same closure-based access as `MirSecretVault`.
+#[derive(Zeroize, ZeroizeOnDrop)]
+pub struct MirSecretVaultG<const K: usize> {
+    secret_s: [[i16; N]; K],
+}
+
+impl<const K: usize> MirSecretVaultG<K> {
+    /// Runs `f` with read-only access to the secret `s` (`K * N` coefficients).
+    pub fn access<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&[i16]) -> R,
+    {
+        f(self.secret_s.as_flattened())
+    }
+}
+
+impl<const K: usize> KeygenVault for MirSecretVaultG<K> {
+    fn is_weak(&self) -> bool {
+        secret_weight(self.secret_s.as_flattened()) < min_secret_weight(K)
+    }
+}
+
+/* === [3. Serialization] === */
+
+/// Writes the public key layout `MAGIC || FORMAT_VERSION || seed || b` for
+/// a `b` of any block count; the one implementation behind both
+/// `MirPubkeyG::write_bytes` and `MirPubkey::to_bytes`.
+///
+/// # Errors
+/// `MirError::BadLength` if `out` is not exactly `HEADER_BYTES + SEED_LEN +
+/// b.len()` long.
+pub(crate) fn write_pubkey(seed: &[u8; SEED_LEN], b: &[u8], out: &mut [u8]) -> Result<(), MirError> {
+    const B: usize = HEADER_BYTES + SEED_LEN;
+    if out.len() != B + b.len() {
+        return Err(MirError::BadLength);
+    }
+    write_header(out);
+    out[HEADER_BYTES..B].copy_from_slice(seed);
+    out[B..].copy_from_slice(b);
+    Ok(())
+}
+
+/// Parses the `write_pubkey` layout into `seed` and `b`.
+///
+/// # Errors
+/// `MirError::BadLength` if `bytes` does not match `b.len()`, then as
+/// `check_header`.
+pub(crate) fn read_pubkey(bytes: &[u8], seed: &mut [u8; SEED_LEN], b: &mut [u8]) -> Result<(), MirError> {
+    const B: usize = HEADER_BYTES + SEED_LEN;
+    if bytes.len() != B + b.len() {
+        return Err(MirError::BadLength);
+    }
+    check_header(bytes)?;
+
+    seed.copy_from_slice(&bytes[HEADER_BYTES..B]);
+    b.copy_from_slice(&bytes[B..]);
+    Ok(())
+}
+
+/// Writes the ciphertext layout `MAGIC || FORMAT_VERSION || u || mask || cnt`
+/// (`cnt` as 2-byte LE); see `write_pubkey`.
+///
+/// # Errors
+/// `MirError::BadLength` if `out` is not exactly `HEADER_BYTES + u.len() +
+/// N / 8 + 2` long.
+pub(crate) fn write_ciphertext(u: &[u8], mask: &[u8; N / 8], cnt: u16, out: &mut [u8]) -> Result<(), MirError> {
+    let m = HEADER_BYTES + u.len();
+    if out.len() != m + N / 8 + 2 {
+        return Err(MirError::BadLength);
+    }
+    write_header(out);
+    out[HEADER_BYTES..m].copy_from_slice(u);
+    out[m..m + N / 8].copy_from_slice(mask);
+    out[m + N / 8..].copy_from_slice(&cnt.to_le_bytes());
+    Ok(())
+}
+
+/// Parses the `write_ciphertext` layout into `u`, `mask` and `cnt`.
+///
+/// # Errors
+/// As `read_pubkey`, and `MirError::OutOfRange` if `cnt > N`.
+pub(crate) fn read_ciphertext(bytes: &[u8], u: &mut [u8], mask: &mut [u8; N / 8], cnt: &mut u16) -> Result<(), MirError> {
+    let m = HEADER_BYTES + u.len();
+    if bytes.len() != m + N / 8 + 2 {
+        return Err(MirError::BadLength);
+    }
+    check_header(bytes)?;
+
+    u.copy_from_slice(&bytes[HEADER_BYTES..m]);
+    mask.copy_from_slice(&bytes[m..m + N / 8]);
+    *cnt = u16::from_le_bytes([bytes[m + N / 8], bytes[m + N / 8 + 1]]);
+    check_cnt(*cnt)
+}
+
+impl<const K: usize> MirPubkeyG<K> {
+    /// Serialized size: `header || seed || b`.
+    pub const BYTES: usize = HEADER_BYTES + SEED_LEN + K * N;
+
+    /// Writes `MAGIC || FORMAT_VERSION || seed || b` into `out`.
+    ///
+    /// # Errors
+    /// `MirError::BadLength` if `out` is not exactly `BYTES` long.
+    pub fn write_bytes(&self, out: &mut [u8]) -> Result<(), MirError> {
+        write_pubkey(&self.seed, self.b.as_flattened(), out)
+    }
+
+    /// Serializes the public key as `MAGIC || FORMAT_VERSION || seed || b`.
+    #[cfg(feature = "alloc")]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = alloc::vec![0u8; Self::BYTES];
+        let _ = self.write_bytes(&mut out);
+        out
+    }
+
+    /// Parses a public key produced by `to_bytes` / `write_bytes`.
+    ///
+    /// # Errors
+    /// `MirError::BadLength` if `bytes` is not exactly `BYTES` long, then
+    /// header errors as for `MirPubkey::from_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MirError> {
+        let mut pk = Self::default();
+        read_pubkey(bytes, &mut pk.seed, pk.b.as_flattened_mut())?;
+        Ok(pk)
+    }
+}
+
+impl<const K: usize> MirCiphertextG<K> {
+    /// Serialized size: `header || u || mask || cnt (2-byte LE)`.
+    pub const BYTES: usize = HEADER_BYTES + K * N + N / 8 + 2;
+
+    /// Same structural check as `MirCiphertext::is_valid`.
+    pub fn is_valid(&self) -> bool {
+        self.cnt as usize <= N && self.cnt == ct::popcount(&self.mask)
+    }
+
+    /// Writes `MAGIC || FORMAT_VERSION || u || mask || cnt` into `out`.
+    ///
+    /// # Errors
+    /// `MirError::BadLength` if `out` is not exactly `BYTES` long.
+    pub fn write_bytes(&self, out: &mut [u8]) -> Result<(), MirError> {
+        write_ciphertext(self.u.as_flattened(), &self.mask, self.cnt, out)
+    }
+
+    /// Serializes the ciphertext as `MAGIC || FORMAT_VERSION || u || mask || cnt`.
+    #[cfg(feature = "alloc")]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = alloc::vec![0u8; Self::BYTES];
+        let _ = self.write_bytes(&mut out);
+        out
+    }
+
+    /// Parses a ciphertext produced by `to_bytes` / `write_bytes`.
+    ///
+    /// # Errors
+    /// As `MirPubkeyG::from_bytes`, and `MirError::OutOfRange` if `cnt > N`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MirError> {
+        let mut ct = Self::default();
+        read_ciphertext(bytes, ct.u.as_flattened_mut(), &mut ct.mask, &mut ct.cnt)?;
+        Ok(ct)
+    }
+}
+
+impl From<&MirPubkey> for MirPubkeyG<{ crate::K }> {
+    fn from(pk: &MirPubkey) -> Self {
+        let mut out = Self { seed: pk.seed, ..Self::default() };
+        out.b.as_flattened_mut().copy_from_slice(&pk.b);
+        out
+    }
+}
+
+impl From<&MirPubkeyG<{ crate::K }>> for MirPubkey {
+    fn from(pk: &MirPubkeyG<{ crate::K }>) -> Self {
+        let mut out = MirPubkey { seed: pk.seed, ..MirPubkey::default() };
+        out.b.copy_from_slice(pk.b.as_flattened());
+        out
+    }
+}
+
+impl From<&MirCiphertext> for MirCiphertextG<{ crate::K }> {
+    fn from(ct: &MirCiphertext) -> Self {
+        let mut out = Self { mask: ct.mask, cnt: ct.cnt, ..Self::default() };
+        out.u.as_flattened_mut().copy_from_slice(&ct.u);
+        out
+    }
+}
+
+impl From<&MirCiphertextG<{ crate::K }>> for MirCiphertext {
+    fn from(ct: &MirCiphertextG<{ crate::K }>) -> Self {
+        let mut out = MirCiphertext { mask: ct.mask, cnt: ct.cnt, ..MirCiphertext::default() };
+        out.u.copy_from_slice(ct.u.as_flattened());
+        out
+    }
+}
+
+/* === [4. Key Exchange] === */
+
+/// `keygen` for rank `K`.
+///
+/// # Panics
+/// As `keygen_with_rng`.
+pub fn keygen_g<const K: usize>() -> (MirPubkeyG<K>, MirSecretVaultG<K>) {
+    keygen_g_with_rng(&mut DefaultRng::default())
+}
+
+/// `keygen_with_rng` for rank `K`, through the same weak-secret re-draw
+/// (at most `KEYGEN_ATTEMPTS` draws, `MIN_SECRET_WEIGHT` taken at rank `K`).
+///
+/// # Panics
+/// As `keygen_with_rng`.
+pub fn keygen_g_with_rng<const K: usize, R: RngCore + CryptoRng>(rng: &mut R) -> (MirPubkeyG<K>, MirSecretVaultG<K>) {
+    match keygen_fresh_with(rng, keygen_from_master::<K>) {
+        Ok(pair) => pair,
+        Err(err) => panic!("Mirletis keygen failed: {:?}", err),
+    }
+}
+
+/// `keygen_from_seed` for rank `K`.
+///
+/// # Errors
+/// As `keygen_from_seed`.
+pub fn keygen_g_from_seed<const K: usize>(seed: &[u8]) -> Result<(MirPubkeyG<K>, MirSecretVaultG<K>), MirError> {
+    keygen_seeded_with(seed, keygen_from_master::<K>)
+}
+
+fn keygen_from_master<const K: usize>(master_seed: &[u8; 64]) -> (MirPubkeyG<K>, MirSecretVaultG<K>) {
+    let () = Rank::<K>::VALID;
+
+    let mut pk = MirPubkeyG::<K>::default();
+    pk.seed.copy_from_slice(&master_seed[..32]);
+
+    let mut vault = MirSecretVaultG { secret_s: [[0i16; N]; K] };
+    gen_secret_from_seed(&master_seed[32..], vault.secret_s.as_flattened_mut());
+
+    // b = A * s, with A expanded onto the stack
+    let mut matrix_a = [[[0i16; N]; K]; K];
+    poly::expand_matrix(&pk.seed, matrix_a.as_flattened_mut().as_flattened_mut());
+    poly::matvec(
+        matrix_a.as_flattened().as_flattened(),
+        vault.secret_s.as_flattened(),
+        pk.b.as_flattened_mut(),
+    );
+
+    (pk, vault)
+}
+
+/// `encaps` for rank `K`.
+pub fn encaps_g<const K: usize>(pk: &MirPubkeyG<K>) -> (MirCiphertextG<K>, MirSharedKey) {
+    encaps_g_with_rng(pk, &mut DefaultRng::default())
+}
+
+/// `encaps_with_rng` for rank `K`.
+pub fn encaps_g_with_rng<const K: usize, R: RngCore + CryptoRng>(
+    pk: &MirPubkeyG<K>,
+    rng: &mut R,
+) -> (MirCiphertextG<K>, MirSharedKey) {
+    let mut eph_seed = ScratchGuard::new([0u8; 32]);
+    rng.fill_bytes(&mut *eph_seed);
+
+    encaps_g_deterministic(pk, &eph_seed)
+}
+
+/// `encaps_deterministic` for rank `K`.
+pub fn encaps_g_deterministic<const K: usize>(pk: &MirPubkeyG<K>, coins: &[u8; 32]) -> (MirCiphertextG<K>, MirSharedKey) {
+    let () = Rank::<K>::VALID;
+
+    let mut r = ScratchGuard::new([[0i16; N]; K]);
+    gen_secret_from_seed(coins, r.as_flattened_mut());
+
+    // u = A^T * r
+    let mut matrix_a = [[[0i16; N]; K]; K];
+    poly::expand_matrix(&pk.seed, matrix_a.as_flattened_mut().as_flattened_mut());
+    let mut ct = MirCiphertextG::<K>::default();
+    poly::matvec_transpose(matrix_a.as_flattened().as_flattened(), r.as_flattened(), ct.u.as_flattened_mut());
+
+    let mut shared = MirSharedKey { key: [0u8; SHARED_LEN] };
+    let cnt = encaps_finish(pk.b.as_flattened(), r.as_flattened(), &mut ct.mask, &[], &mut shared.key);
+    ct.cnt = poly::unwrap_kernel(cnt);
+
+    (ct, shared)
+}
+
+/// `decaps` for rank `K`.
+pub fn decaps_g<const K: usize>(ct: &MirCiphertextG<K>, vault: &MirSecretVaultG<K>) -> MirSharedKey {
+    let mut shared = MirSharedKey { key: [0u8; SHARED_LEN] };
+    poly::unwrap_kernel(vault.access(|s| decaps_core(ct.u.as_flattened(), &ct.mask, s, &[], &mut shared.key)));
+    shared
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decaps, encaps_deterministic, keygen_from_seed, K};
+
+    #[test]
+    fn test_generic_matches_fixed() {
+        let (pk, vault) = keygen_from_seed(&[0x51u8; 32]).unwrap();
+        let (pk_g, vault_g) = keygen_g_from_seed::<K>(&[0x51u8; 32]).unwrap();
+        assert_eq!(pk_g.seed, pk.seed);
+        assert_eq!(pk_g.b.as_flattened(), &pk.b[..]);
+        assert!(vault_g.access(|s| vault.access(|t| s == t)));
+
+        let (ct, key) = encaps_deterministic(&pk, &[0x52u8; 32]);
+        let (ct_g, key_g) = encaps_g_deterministic(&pk_g, &[0x52u8; 32]);
+        assert_eq!(key_g.key, key.key);
+        assert_eq!(MirCiphertext::from(&ct_g).to_bytes(), ct.to_bytes());
+        assert_eq!(decaps_g(&MirCiphertextG::from(&ct), &vault_g).key, key.key);
+        assert_eq!(decaps(&MirCiphertext::from(&ct_g), &vault).key, key.key);
+
+        // Same byte layouts
+        let mut buf = [0u8; crate::PUBKEY_BYTES];
+        pk_g.write_bytes(&mut buf).unwrap();
+        assert_eq!(buf, pk.to_bytes());
+        assert_eq!(MirPubkey::from(&pk_g).to_bytes(), pk.to_bytes());
+        assert_eq!(MirPubkeyG::<K>::from(&pk).seed, pk.seed);
+        let mut buf = [0u8; crate::CIPHERTEXT_BYTES];
+        ct_g.write_bytes(&mut buf).unwrap();
+        assert_eq!(buf, ct.to_bytes());
+    }
+
+    #[test]
+    fn test_generic_ranks_side_by_side() {
+        let (pk2, vault2) = keygen_g::<2>();
+        let (pk3, vault3) = keygen_g::<3>();
+        let (ct2, key2) = encaps_g(&pk2);
+        let (ct3, key3) = encaps_g(&pk3);
+        assert_eq!(decaps_g(&ct2, &vault2).key, key2.key);
+        assert_eq!(decaps_g(&ct3, &vault3).key, key3.key);
+        assert!(ct2.is_valid() && ct3.is_valid());
+
+        assert_eq!(MirPubkeyG::<2>::BYTES, HEADER_BYTES + SEED_LEN + 2 * N);
+        assert_eq!(MirCiphertextG::<3>::BYTES, HEADER_BYTES + 3 * N + N / 8 + 2);
+
+        let mut buf = [0u8; MirPubkeyG::<2>::BYTES];
+        pk2.write_bytes(&mut buf).unwrap();
+        assert_eq!(MirPubkeyG::<2>::from_bytes(&buf).unwrap().b, pk2.b);
+        assert_eq!(MirPubkeyG::<3>::from_bytes(&buf).err(), Some(MirError::BadLength));
+
+        let mut buf = [0u8; MirCiphertextG::<3>::BYTES];
+        ct3.write_bytes(&mut buf).unwrap();
+        let ct3_back = MirCiphertextG::<3>::from_bytes(&buf).unwrap();
+        assert_eq!(decaps_g(&ct3_back, &vault3).key, key3.key);
+        assert_eq!(pk3.write_bytes(&mut buf).err(), Some(MirError::BadLength));
+    }
+}
@@ -0,0 +1,296 @@
+/*
+ * Stage-by-stage known-answer tests (`kat` feature).
+ *
+ * The v1 blobs pin the end-to-end output of one exchange; these vectors pin
+ * every intermediate of it, so a regression in one loop fails the stage
+ * that owns the loop instead of a single "shared key changed". Stages:
+ *
+ *   master   SHAKE256(KEYGEN || seed), 64 bytes
+ *   matrix   SHA3-256 over A (i16 LE, as laid out by expand_matrix) for master[..32]
+ *   s, r     gen_secret_from_seed(master[32..]) and (coins), one byte per coefficient
+ *   b        round(A * s)        matvec (and matvec_streamed at rank K)
+ *   u        round(A^T * r)      matvec_transpose (and its streamed twin)
+ *   v        b * r               inner_product
+ *   mask     safe zone of v      select_safe_zone, with cnt (2-byte LE)
+ *   v_prime  u * s               inner_product
+ *   shared   KDF over v, and over v_prime, with mask and cnt
+ *
+ * Every stage reads its inputs from the file, never from an earlier stage,
+ * so one broken loop fails exactly one test. The one exception is A, which
+ * is pinned by digest only and re-expanded for `b` and `u` (a broken
+ * expansion fails `matrix` first). The vectors cover rank 2 as
+ * well as the crate's rank: index math that uses `K` where it must use the
+ * block count of its input only shows up away from `K`, and at any rank
+ * above 1 a transposed index breaks `b` or `u`.
+ */
+
+use sha3::digest::XofReader;
+use sha3::{Digest, Sha3_256};
+
+use crate::{
+    decaps_g, derive_shared_bytes, domains, encaps_g_deterministic, gen_secret_from_seed, keygen_g_from_seed,
+    mir_shake_reader, poly, select_safe_zone, K, N, SEED_LEN, SHARED_LEN,
+};
+
+/// The vectors of one rank, as `name = hex` lines.
+struct Vectors {
+    rank: usize,
+    text: &'static str,
+}
+
+const RANKS: [Vectors; 2] = [
+    Vectors { rank: 2, text: include_str!("../testdata/kat/rank2.kat") },
+    Vectors { rank: 5, text: include_str!("../testdata/kat/rank5.kat") },
+];
+
+const _: () = assert!(K == 5, "testdata/kat/rank5.kat is the crate's rank");
+
+impl Vectors {
+    /// Decodes the value of `name` into `out`, which must be exactly its length.
+    fn read(&self, name: &str, out: &mut [u8]) {
+        let hex = self
+            .text
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.trim_start().strip_prefix('='))
+            .unwrap_or_else(|| panic!("KAT rank {}: no `{}` vector", self.rank, name))
+            .trim();
+        assert_eq!(hex.len(), 2 * out.len(), "KAT rank {}: `{}` has the wrong length", self.rank, name);
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+    }
+
+    /// Reads a ternary vector, stored as one two's-complement byte per coefficient.
+    fn read_ternary(&self, name: &str, out: &mut [i16]) {
+        let mut bytes = [0u8; K * N];
+        self.read(name, &mut bytes[..out.len()]);
+        for (c, &byte) in out.iter_mut().zip(bytes.iter()) {
+            *c = byte as i8 as i16;
+        }
+    }
+
+    fn seed(&self) -> [u8; SEED_LEN] {
+        let mut master = [0u8; 64];
+        self.read("master", &mut master);
+        let mut seed = [0u8; SEED_LEN];
+        seed.copy_from_slice(&master[..SEED_LEN]);
+        seed
+    }
+
+    /// Fails `stage` at the first differing index, so the report names both
+    /// the loop and the coefficient it got wrong.
+    fn check<T: PartialEq + core::fmt::Debug>(&self, stage: &str, got: &[T], want: &[T]) {
+        assert_eq!(got.len(), want.len(), "KAT rank {}: stage `{}` has the wrong length", self.rank, stage);
+        if let Some(i) = (0..want.len()).find(|&i| got[i] != want[i]) {
+            panic!(
+                "KAT rank {}: stage `{}` differs at index {} (got {:?}, want {:?})",
+                self.rank, stage, i, got[i], want[i]
+            );
+        }
+    }
+}
+
+#[test]
+fn kat_master_seed() {
+    for v in &RANKS {
+        let mut seed = [0u8; 32];
+        v.read("seed", &mut seed);
+        let mut want = [0u8; 64];
+        v.read("master", &mut want);
+
+        let mut got = [0u8; 64];
+        mir_shake_reader(&seed, domains::KEYGEN).read(&mut got);
+        v.check("master", &got, &want);
+    }
+}
+
+#[test]
+fn kat_matrix_expansion() {
+    for v in &RANKS {
+        let mut want = [0u8; 32];
+        v.read("matrix", &mut want);
+
+        let mut a = [0i16; K * K * N];
+        let a = &mut a[..v.rank * v.rank * N];
+        poly::expand_matrix(&v.seed(), a);
+
+        let mut hasher = Sha3_256::new();
+        for c in a.iter() {
+            Digest::update(&mut hasher, c.to_le_bytes());
+        }
+        v.check("matrix", &hasher.finalize()[..], &want);
+    }
+}
+
+#[test]
+fn kat_secret_sampling() {
+    for v in &RANKS {
+        let len = v.rank * N;
+        let mut master = [0u8; 64];
+        v.read("master", &mut master);
+        let mut coins = [0u8; 32];
+        v.read("coins", &mut coins);
+
+        let (mut want, mut got) = ([0i16; K * N], [0i16; K * N]);
+        v.read_ternary("s", &mut want[..len]);
+        gen_secret_from_seed(&master[32..], &mut got[..len]);
+        v.check("s", &got[..len], &want[..len]);
+
+        v.read_ternary("r", &mut want[..len]);
+        gen_secret_from_seed(&coins, &mut got[..len]);
+        v.check("r", &got[..len], &want[..len]);
+    }
+}
+
+#[test]
+fn kat_matvec_b() {
+    for v in &RANKS {
+        let len = v.rank * N;
+        let mut s = [0i16; K * N];
+        v.read_ternary("s", &mut s[..len]);
+        let mut want = [0u8; K * N];
+        v.read("b", &mut want[..len]);
+
+        let mut a = [0i16; K * K * N];
+        poly::expand_matrix(&v.seed(), &mut a[..v.rank * len]);
+        let mut got = [0u8; K * N];
+        poly::matvec(&a[..v.rank * len], &s[..len], &mut got[..len]);
+        v.check("b", &got[..len], &want[..len]);
+
+        // The streamed kernels are fixed to the crate's rank
+        if v.rank == K {
+            let mut got = [0u8; K * N];
+            poly::matvec_streamed(&v.seed(), &s, &mut got);
+            v.check("b (streamed)", &got, &want);
+        }
+    }
+}
+
+#[test]
+fn kat_matvec_transpose_u() {
+    for v in &RANKS {
+        let len = v.rank * N;
+        let mut r = [0i16; K * N];
+        v.read_ternary("r", &mut r[..len]);
+        let mut want = [0u8; K * N];
+        v.read("u", &mut want[..len]);
+
+        let mut a = [0i16; K * K * N];
+        poly::expand_matrix(&v.seed(), &mut a[..v.rank * len]);
+        let mut got = [0u8; K * N];
+        poly::matvec_transpose(&a[..v.rank * len], &r[..len], &mut got[..len]);
+        v.check("u", &got[..len], &want[..len]);
+
+        // The streamed kernels are fixed to the crate's rank
+        if v.rank == K {
+            let mut got = [0u8; K * N];
+            poly::matvec_transpose_streamed(&v.seed(), &r, &mut got);
+            v.check("u (streamed)", &got, &want);
+        }
+    }
+}
+
+#[test]
+fn kat_inner_product_v() {
+    for v in &RANKS {
+        let len = v.rank * N;
+        let mut b = [0u8; K * N];
+        v.read("b", &mut b[..len]);
+        let mut r = [0i16; K * N];
+        v.read_ternary("r", &mut r[..len]);
+        let mut want = [0u8; N];
+        v.read("v", &mut want);
+
+        let mut got = [0u8; N];
+        poly::inner_product(&b[..len], &r[..len], &mut got);
+        v.check("v", &got, &want);
+    }
+}
+
+#[test]
+fn kat_safe_zone_mask() {
+    for v in &RANKS {
+        let mut values = [0u8; N];
+        v.read("v", &mut values);
+        let mut want = [0u8; N / 8];
+        v.read("mask", &mut want);
+        let mut cnt = [0u8; 2];
+        v.read("cnt", &mut cnt);
+
+        let mut got = [0xFFu8; N / 8];
+        let got_cnt = select_safe_zone(&values, &mut got);
+        v.check("mask", &got, &want);
+        v.check("cnt", &got_cnt.to_le_bytes(), &cnt);
+    }
+}
+
+#[test]
+fn kat_inner_product_v_prime() {
+    for v in &RANKS {
+        let len = v.rank * N;
+        let mut u = [0u8; K * N];
+        v.read("u", &mut u[..len]);
+        let mut s = [0i16; K * N];
+        v.read_ternary("s", &mut s[..len]);
+        let mut want = [0u8; N];
+        v.read("v_prime", &mut want);
+
+        let mut got = [0u8; N];
+        poly::inner_product(&u[..len], &s[..len], &mut got);
+        v.check("v_prime", &got, &want);
+    }
+}
+
+#[test]
+fn kat_kdf() {
+    for v in &RANKS {
+        let mut mask = [0u8; N / 8];
+        v.read("mask", &mut mask);
+        let mut cnt = [0u8; 2];
+        v.read("cnt", &mut cnt);
+        let cnt = u16::from_le_bytes(cnt);
+        let mut want = [0u8; SHARED_LEN];
+        v.read("shared", &mut want);
+
+        // Both sides reconcile to the same bits, so both derive `shared`
+        for (name, stage) in [("v", "shared (from v)"), ("v_prime", "shared (from v_prime)")] {
+            let mut values = [0u8; N];
+            v.read(name, &mut values);
+            let mut got = [0u8; SHARED_LEN];
+            derive_shared_bytes(&values, &mask, cnt, &[], &mut got);
+            v.check(stage, &got, &want);
+        }
+    }
+}
+
+/// The public entry points for rank `R`, against the same vectors.
+fn end_to_end<const R: usize>(v: &Vectors) {
+    assert_eq!(v.rank, R);
+    let mut seed = [0u8; 32];
+    v.read("seed", &mut seed);
+    let mut coins = [0u8; 32];
+    v.read("coins", &mut coins);
+    let (mut b, mut u) = ([0u8; K * N], [0u8; K * N]);
+    v.read("b", &mut b[..R * N]);
+    v.read("u", &mut u[..R * N]);
+    let mut mask = [0u8; N / 8];
+    v.read("mask", &mut mask);
+    let mut shared = [0u8; SHARED_LEN];
+    v.read("shared", &mut shared);
+
+    let (pk, vault) = keygen_g_from_seed::<R>(&seed).unwrap();
+    v.check("keygen_g_from_seed", pk.b.as_flattened(), &b[..R * N]);
+
+    let (ct, key) = encaps_g_deterministic(&pk, &coins);
+    v.check("encaps_g_deterministic (u)", ct.u.as_flattened(), &u[..R * N]);
+    v.check("encaps_g_deterministic (mask)", &ct.mask, &mask);
+    v.check("encaps_g_deterministic (shared)", &key.key, &shared);
+
+    v.check("decaps_g", &decaps_g(&ct, &vault).key, &shared);
+}
+
+#[test]
+fn kat_end_to_end() {
+    end_to_end::<2>(&RANKS[0]);
+    end_to_end::<5>(&RANKS[1]);
+}
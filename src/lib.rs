@@ -8,26 +8,248 @@
  */
 
 #![no_std]
+#[cfg(feature = "std")]
+extern crate std;
+#[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(feature = "alloc")]
 use alloc::vec;
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
 
-use sha3::{Shake256, Sha3_256, Digest, digest::{Update, ExtendableOutput, XofReader}};
-use rand::{RngCore, rngs::OsRng};
+use sha3::{Shake256, Shake256Reader, Sha3_256, Digest, digest::{Update, ExtendableOutput, XofReader}};
+use rand::{CryptoRng, RngCore};
 use zeroize::{Zeroize, ZeroizeOnDrop};
+#[cfg(feature = "alloc")]
+use zeroize::Zeroizing;
 
 /* === [1. Constants] === */
 pub const N: usize = 256;
 pub const K: usize = 5;
+/// Mask of a 13-bit sample. With the power-of-two modulus it is also the
+/// reduction mask; with `prime-modulus` it only bounds matrix samples.
 pub const Q_MASK: i32 = 0x1FFF;
+/// The modulus `q`: `2^13`, or the Mersenne prime `2^13 - 1` with `prime-modulus`.
+#[cfg(not(feature = "prime-modulus"))]
+pub const Q: i32 = Q_MASK + 1;
+/// The modulus `q`: `2^13`, or the Mersenne prime `2^13 - 1` with `prime-modulus`.
+#[cfg(feature = "prime-modulus")]
+pub const Q: i32 = Q_MASK;
 pub const SHIFT: u32 = 5;
+/// Bit of a rounded coefficient in `Z_p` that carries a reconciled key bit,
+/// `(v >> RECON_BIT) & 1`, on both the encapsulating and decapsulating side.
+pub const RECON_BIT: u32 = 6;
 pub const SEED_LEN: usize = 32;
 pub const SHARED_LEN: usize = 32;
+/// Minimum length of any seed accepted by the seeded constructors.
+pub const MIN_SEED_LEN: usize = 32;
+
+/// Minimum number of nonzero coefficients in a freshly generated `s`.
+///
+/// `s` has `K * N` coefficients, each nonzero with probability 1/2, so the
+/// expected weight is `K * N / 2`. A secret below a quarter of `K * N` occurs
+/// with probability below 2^-115 for a sound RNG; seeing one means the
+/// entropy source is suspect, and is never worth keeping.
+pub const MIN_SECRET_WEIGHT: usize = min_secret_weight(K);
+
+/// `MIN_SECRET_WEIGHT` for a secret of `k` blocks (see `MirSecretVaultG`).
+const fn min_secret_weight(k: usize) -> usize {
+    k * N / 4
+}
+
+/// Number of fresh draws `keygen` makes before giving up on a weak secret.
+pub const KEYGEN_ATTEMPTS: usize = 4;
+
+/// Magic prefix of every serialized public key and ciphertext.
+pub const MAGIC: [u8; 4] = *b"MIRL";
+/// Format version byte written after `MAGIC`.
+///
+/// The low six bits count layout revisions (currently 1). The top bits mark
+/// wire-incompatible builds, so their blobs are rejected instead of being
+/// misread: `0x80` for `prime-modulus`, `0x40` for `aes-expand`, `0x20` for
+/// `padded-kdf`.
+pub const FORMAT_VERSION: u8 = 0x01
+    | if cfg!(feature = "prime-modulus") { 0x80 } else { 0x00 }
+    | if cfg!(feature = "aes-expand") { 0x40 } else { 0x00 }
+    | if cfg!(feature = "padded-kdf") { 0x20 } else { 0x00 };
+/// Size of the `MAGIC || FORMAT_VERSION` header.
+pub const HEADER_BYTES: usize = MAGIC.len() + 1;
+
+/// Serialized public key size (`MirPubkey::to_bytes`): `header || seed || b`.
+pub const PUBKEY_BYTES: usize = HEADER_BYTES + SEED_LEN + K * N;
+/// Serialized ciphertext size (`MirCiphertext::to_bytes`): `header || u || mask || cnt`.
+pub const CIPHERTEXT_BYTES: usize = HEADER_BYTES + K * N + N / 8 + 2;
+/// Bits per coefficient of `b` in `MirPubkey::to_bytes_compressed`.
+///
+/// `b` is a 13-bit value rounded down by `SHIFT`, so only its low
+/// `13 - SHIFT` bits can be set. At `SHIFT = 5` that is the full byte and the
+/// compressed form is as large as `to_bytes`; it shrinks as `SHIFT` grows.
+pub const B_BITS: u32 = Q_MASK.count_ones() - SHIFT;
+/// Serialized public key size (`MirPubkey::to_bytes_compressed`):
+/// `header || seed || b` with `b` packed at `B_BITS` bits per coefficient.
+pub const PUBKEY_COMPRESSED_BYTES: usize = HEADER_BYTES + SEED_LEN + (K * N * B_BITS as usize).div_ceil(8);
+const _: () = assert!((Q - 1) >> SHIFT < 1 << B_BITS, "b coefficients must fit in B_BITS");
+/// Size of the ciphertext integrity tag (`mac` feature).
+#[cfg(feature = "mac")]
+pub const MAC_LEN: usize = 32;
+/// Serialized tagged ciphertext size (`MirCiphertext::to_bytes_with_mac`): `ct || tag`.
+#[cfg(feature = "mac")]
+pub const CIPHERTEXT_MAC_BYTES: usize = CIPHERTEXT_BYTES + MAC_LEN;
+/// Size of a secret-key seed (`MirSecretSeed`).
+pub const SECRET_SEED_BYTES: usize = 32;
+
+/// Effective parameter set of this build.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Params {
+    pub n: usize,
+    pub k: usize,
+    pub q: i32,
+    pub q_mask: i32,
+    pub shift: u32,
+    pub seed_len: usize,
+    pub shared_len: usize,
+}
+
+/// Returns the compiled-in parameters, for sizing buffers and logging.
+pub const fn params() -> Params {
+    Params {
+        n: N,
+        k: K,
+        q: Q,
+        q_mask: Q_MASK,
+        shift: SHIFT,
+        seed_len: SEED_LEN,
+        shared_len: SHARED_LEN,
+    }
+}
+
+/// Every optional Cargo feature, paired with whether this build enables it.
+const FEATURES: [(&str, bool); 16] = [
+    ("std", cfg!(feature = "std")),
+    ("alloc", cfg!(feature = "alloc")),
+    ("heapless", cfg!(feature = "heapless")),
+    ("wasm", cfg!(feature = "wasm")),
+    ("rayon", cfg!(feature = "rayon")),
+    ("cbor", cfg!(feature = "cbor")),
+    ("bench", cfg!(feature = "bench")),
+    ("aes-expand", cfg!(feature = "aes-expand")),
+    ("prime-modulus", cfg!(feature = "prime-modulus")),
+    ("testing", cfg!(feature = "testing")),
+    ("ct-test", cfg!(feature = "ct-test")),
+    ("subtle", cfg!(feature = "subtle")),
+    ("mac", cfg!(feature = "mac")),
+    ("debug-bounds", cfg!(feature = "debug-bounds")),
+    ("kat", cfg!(feature = "kat")),
+    ("padded-kdf", cfg!(feature = "padded-kdf")),
+];
+
+const ENABLED_COUNT: usize = {
+    let mut count = 0;
+    let mut i = 0;
+    while i < FEATURES.len() {
+        count += FEATURES[i].1 as usize;
+        i += 1;
+    }
+    count
+};
+
+static ENABLED: [&str; ENABLED_COUNT] = {
+    let mut out = [""; ENABLED_COUNT];
+    let mut count = 0;
+    let mut i = 0;
+    while i < FEATURES.len() {
+        if FEATURES[i].1 {
+            out[count] = FEATURES[i].0;
+            count += 1;
+        }
+        i += 1;
+    }
+    out
+};
+
+/// Names of the optional Cargo features this build was compiled with, for
+/// logs and bug reports (several change wire format or code paths).
+pub fn enabled_features() -> &'static [&'static str] {
+    &ENABLED
+}
 
 /* Domain Separation Tags */
-const DOM_MATRIX: u8 = 0x00;
-const DOM_SECRET: u8 = 0x01;
-const DOM_HASH:   u8 = 0x02;
+
+/// Every hash/XOF invocation is prefixed with exactly one of these tags.
+///
+/// Allocation:
+/// - `0x00..=0x0F`: core KEM (matrix, secret, KDF, keygen, fingerprints)
+/// - `0x10..=0x1F`: reserved for hybrid combiners
+/// - `0x20..=0x2F`: reserved for AEAD / transport layers
+/// - `0x30..=0x3F`: reserved for subkey derivation
+/// - `0x40..=0x4F`: feature-gated constructions layered on the core KEM
+///   (`mac`, `padded-kdf`)
+///
+/// New subsystems take the next free tag from their range and add it to
+/// `ALL`; reusing `HASH` for anything but the shared-key KDF is not allowed.
+/// `0x0E..=0x0F` stay free for the core KEM itself: anything behind an
+/// optional feature goes to `0x40..=0x4F` (`MATRIX_AES` predates this rule),
+/// and a full range gets a new documented one rather than a neighbour's tag.
+///
+/// The `prime-modulus` build is wire-incompatible with the default one, so
+/// it sets the top bit (`VARIANT`) of every tag.
+mod domains {
+    #[cfg(not(feature = "prime-modulus"))]
+    const VARIANT: u8 = 0x00;
+    #[cfg(feature = "prime-modulus")]
+    const VARIANT: u8 = 0x80;
+
+    #[allow(clippy::identity_op)] // Keeps the base value visible next to the others.
+    pub const MATRIX: u8 = VARIANT | 0x00;
+    pub const SECRET: u8 = VARIANT | 0x01;
+    pub const HASH: u8 = VARIANT | 0x02;
+    pub const MATRIX_AES: u8 = VARIANT | 0x03;
+    pub const KEYGEN: u8 = VARIANT | 0x04;
+    pub const FINGERPRINT_META: u8 = VARIANT | 0x05;
+    pub const FINGERPRINT: u8 = VARIANT | 0x06;
+    pub const TRANSCRIPT: u8 = VARIANT | 0x07;
+    pub const ENCAPS_DET: u8 = VARIANT | 0x08;
+    pub const ZBASE32_CHECK: u8 = VARIANT | 0x09;
+    pub const HASH_CTX: u8 = VARIANT | 0x0A;
+    pub const KEYGEN_INDEX: u8 = VARIANT | 0x0B;
+    pub const COMBINE: u8 = VARIANT | 0x0C;
+    pub const RATCHET: u8 = VARIANT | 0x0D;
+
+    pub const MAC: u8 = VARIANT | 0x40;
+    pub const HASH_PADDED: u8 = VARIANT | 0x41;
+
+    const ALL: [u8; 16] = [
+        MATRIX, SECRET, HASH, MATRIX_AES, KEYGEN, FINGERPRINT_META, FINGERPRINT, TRANSCRIPT, ENCAPS_DET,
+        ZBASE32_CHECK, HASH_CTX, KEYGEN_INDEX, COMBINE, RATCHET, MAC, HASH_PADDED,
+    ];
+
+    const fn all_distinct(tags: &[u8]) -> bool {
+        let mut i = 0;
+        while i < tags.len() {
+            let mut j = i + 1;
+            while j < tags.len() {
+                if tags[i] == tags[j] {
+                    return false;
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+        true
+    }
+
+    const _: () = assert!(all_distinct(&ALL), "domain tags must be distinct");
+
+    const fn in_range(tag: u8, lo: u8, hi: u8) -> bool {
+        let base = tag & !VARIANT;
+        base >= lo && base <= hi
+    }
+
+    const _: () = assert!(
+        in_range(MAC, 0x40, 0x4F) && in_range(HASH_PADDED, 0x40, 0x4F),
+        "feature-gated tags belong in 0x40..=0x4F"
+    );
+}
 
 /* === [2. Data Structures] === */
 
@@ -48,6 +270,77 @@ impl Default for MirPubkey {
     }
 }
 
+impl MirPubkey {
+    /// Reassembles a public key from its matrix seed and `b` vector.
+    pub fn from_parts(seed: [u8; SEED_LEN], b: [u8; K * N]) -> Self {
+        Self { seed, b }
+    }
+
+    /// Returns the seed from which matrix `A` is expanded.
+    pub fn seed(&self) -> &[u8; SEED_LEN] {
+        &self.seed
+    }
+
+    /// Returns the rounded vector `b = round(A * s)`.
+    pub fn b(&self) -> &[u8; K * N] {
+        &self.b
+    }
+
+    /// SHA3-256 over `seed || b` under a dedicated domain tag.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        mir_sha3_256(&mut out, &[&self.seed, &self.b], domains::FINGERPRINT);
+        out
+    }
+
+    /// SHA3-256 over `seed || b || metadata` under a dedicated domain tag.
+    ///
+    /// Binds context such as a key ID or validity period into the identifier:
+    /// the same key with different metadata yields a different fingerprint.
+    /// `seed` and `b` are fixed-length, so the split is unambiguous.
+    pub fn fingerprint_with_metadata(&self, metadata: &[u8]) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        mir_sha3_256(&mut out, &[&self.seed, &self.b, metadata], domains::FINGERPRINT_META);
+        out
+    }
+
+    /// Constant-time equality over the serialized form (`seed || b`).
+    ///
+    /// Public keys are not secret, so plain comparisons (`to_bytes() ==`)
+    /// are fine for lookups and deduplication. Use this instead when the
+    /// comparison authenticates a peer, e.g. checking a received key against
+    /// a pinned one, where an early exit would reveal how long a prefix of a
+    /// forged key was correct.
+    pub fn ct_eq(&self, other: &Self) -> bool {
+        ct::eq_slice(&self.to_bytes(), &other.to_bytes())
+    }
+
+    /// Counts how often each byte value occurs in `b`, as a health check.
+    ///
+    /// An honest `b` is close to uniform over `Z_p`, about `K * N / 256` per
+    /// value. A histogram dominated by a few values points at a faulty
+    /// generator or RNG. Purely diagnostic; `b` is public.
+    pub fn coefficient_histogram(&self) -> [u32; 256] {
+        let mut hist = [0u32; 256];
+        for &v in self.b.iter() {
+            hist[v as usize] += 1;
+        }
+        hist
+    }
+}
+
+/// Short human-readable identifier: `Mirletis/` followed by the first
+/// 8 bytes of `fingerprint()` in hex.
+impl core::fmt::Display for MirPubkey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("Mirletis/")?;
+        for byte in &self.fingerprint()[..8] {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
 /// Mirletis Ciphertext.
 /// Contains the vector u, the safe-zone mask, and the count of valid bits.
 #[derive(Clone, Copy)]
@@ -57,6 +350,93 @@ pub struct MirCiphertext {
     pub cnt: u16,
 }
 
+impl Default for MirCiphertext {
+    fn default() -> Self {
+        Self {
+            u: [0u8; K * N],
+            mask: [0u8; N / 8],
+            cnt: 0,
+        }
+    }
+}
+
+impl MirCiphertext {
+    /// Structural check: `cnt` matches the number of bits set in `mask`
+    /// (and therefore `cnt <= N`).
+    ///
+    /// This is a cheap pre-filter and says nothing about whether the
+    /// ciphertext decapsulates to the sender's key.
+    pub fn is_valid(&self) -> bool {
+        self.cnt as usize <= N && self.cnt == ct::popcount(&self.mask)
+    }
+
+    /// The `is_valid` check, telling the two failures apart.
+    ///
+    /// # Errors
+    /// `MirError::OutOfRange` if `cnt > N` and `MirError::InconsistentCount`
+    /// if it does not match the mask.
+    pub fn check(&self) -> Result<(), MirError> {
+        check_cnt(self.cnt)?;
+        if self.cnt != ct::popcount(&self.mask) {
+            return Err(MirError::InconsistentCount);
+        }
+        Ok(())
+    }
+
+    /// Repairs `cnt` by recomputing it from `mask`.
+    pub fn canonicalize(&mut self) {
+        self.cnt = ct::popcount(&self.mask);
+    }
+
+    /// Constant-time select: returns `a` if `cond == 1`, `b` if `cond == 0`.
+    ///
+    /// Every byte of both inputs is read and combined through a mask, so
+    /// neither the timing nor the memory access pattern depends on `cond`.
+    /// `cond` must be exactly 0 or 1.
+    pub fn ct_select(a: &Self, b: &Self, cond: u32) -> Self {
+        let mut out = MirCiphertext::default();
+        let mut i = 0;
+        while i < K * N {
+            out.u[i] = ct::sel_u8(a.u[i], b.u[i], cond);
+            i += 1;
+        }
+        let mut i = 0;
+        while i < N / 8 {
+            out.mask[i] = ct::sel_u8(a.mask[i], b.mask[i], cond);
+            i += 1;
+        }
+        out.cnt = ct::sel_u16(a.cnt, b.cnt, cond);
+        out
+    }
+
+    /// Failure injection (`testing` feature): flips bit `index` of `region`.
+    ///
+    /// Bits are numbered LSB-first within each byte, matching the mask layout.
+    /// `cnt` is left as is, so a `Mask` flip is exactly what `decaps_checked`
+    /// must catch, while a `U` flip passes the structural check.
+    ///
+    /// # Panics
+    /// If `index` is past the end of the region (`K * N * 8` or `N` bits).
+    #[cfg(feature = "testing")]
+    pub fn flip_bit(&mut self, region: Region, index: usize) {
+        match region {
+            Region::U => self.u[index >> 3] ^= 1 << (index & 7),
+            Region::Mask => {
+                assert!(index < N, "mask bit out of range");
+                self.mask[index >> 3] ^= 1 << (index & 7);
+            }
+        }
+    }
+}
+
+/// Ciphertext component targeted by `MirCiphertext::flip_bit`.
+#[cfg(feature = "testing")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    U,
+    Mask,
+}
+
 /// Shared Secret Key.
 /// Result of the Key Encapsulation Mechanism.
 #[derive(Clone, Zeroize, ZeroizeOnDrop)]
@@ -64,42 +444,488 @@ pub struct MirSharedKey {
     pub key: [u8; SHARED_LEN],
 }
 
+impl MirSharedKey {
+    /// Combines the keys of two exchanges into one, e.g. when initiator and
+    /// responder each encapsulate to the other.
+    ///
+    /// Returns `SHA3-256(COMBINE || lo || hi || transcript)`, where `lo` and
+    /// `hi` are the two keys sorted in constant time, so both parties get the
+    /// same result whichever key they call this on. `transcript` should bind
+    /// whatever the protocol needs (identities, both ciphertexts, ...).
+    pub fn combine(&self, other: &Self, transcript: &[u8]) -> MirSharedKey {
+        let swap = ct::lt_slice(&other.key, &self.key);
+        let mut lo = ScratchGuard::new([0u8; SHARED_LEN]);
+        let mut hi = ScratchGuard::new([0u8; SHARED_LEN]);
+        let mut i = 0;
+        while i < SHARED_LEN {
+            lo[i] = ct::sel_u8(other.key[i], self.key[i], swap);
+            hi[i] = ct::sel_u8(self.key[i], other.key[i], swap);
+            i += 1;
+        }
+
+        let mut out = MirSharedKey { key: [0u8; SHARED_LEN] };
+        mir_sha3_256(&mut out.key, &[&*lo, &*hi, transcript], domains::COMBINE);
+        out
+    }
+
+    /// Re-keys in place: the key becomes `SHAKE256(RATCHET || key || info)`.
+    ///
+    /// The old value is zeroized before the new one is written, so a key
+    /// compromised later does not reveal the keys before it. Both parties
+    /// stay in sync as long as they ratchet with the same `info` sequence.
+    pub fn ratchet(&mut self, info: &[u8]) {
+        let mut next = ScratchGuard::new([0u8; SHARED_LEN]);
+        let mut hasher = Shake256::default();
+        hasher.update(&[domains::RATCHET]);
+        hasher.update(&self.key);
+        hasher.update(info);
+        hasher.finalize_xof().read(&mut *next);
+
+        self.key.zeroize();
+        self.key.copy_from_slice(&*next);
+    }
+}
+
+/// `subtle` integration: `MirSharedKey` zeroizes on drop, so it cannot be
+/// `Copy` as `ConditionallySelectable` requires; the select is inherent.
+#[cfg(feature = "subtle")]
+impl MirSharedKey {
+    /// Returns `a` if `choice` is 0 and `b` if it is 1 (`subtle` convention),
+    /// reading every byte of both.
+    pub fn conditional_select(a: &Self, b: &Self, choice: subtle::Choice) -> Self {
+        use subtle::ConditionallySelectable;
+        let mut out = MirSharedKey { key: [0u8; SHARED_LEN] };
+        let mut i = 0;
+        while i < SHARED_LEN {
+            out.key[i] = u8::conditional_select(&a.key[i], &b.key[i], choice);
+            i += 1;
+        }
+        out
+    }
+}
+
+#[cfg(feature = "subtle")]
+impl subtle::ConstantTimeEq for MirSharedKey {
+    fn ct_eq(&self, other: &Self) -> subtle::Choice {
+        self.key.ct_eq(&other.key)
+    }
+}
+
+/// Same operation as `MirCiphertext::ct_select`, in the `subtle` argument order.
+#[cfg(feature = "subtle")]
+impl subtle::ConditionallySelectable for MirCiphertext {
+    fn conditional_select(a: &Self, b: &Self, choice: subtle::Choice) -> Self {
+        MirCiphertext::ct_select(b, a, choice.unwrap_u8() as u32)
+    }
+}
+
+/// Diagnostic information about a single encapsulation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncapsInfo {
+    /// Number of safe-zone bits that fed the KDF (equal to `MirCiphertext::cnt`).
+    pub safe_bits: u16,
+}
+
+/// Aggregate reconciliation statistics over many ciphertexts (`testing` only).
+///
+/// Feed it ciphertexts with `observe` and read a `ReconSummary` back, e.g.
+/// to tune the safe-zone threshold or centers against the observed `cnt`
+/// distribution. Only public ciphertext fields are read. The distance of
+/// each `v` coefficient to its zone center is *not* recoverable from public
+/// data; measure it on the encapsulating side if needed.
+#[cfg(feature = "testing")]
+#[derive(Clone, Debug)]
+pub struct ReconStats {
+    observed: u64,
+    cnt_histogram: [u32; N + 1],
+    selected: [u32; N],
+}
+
+/// Snapshot returned by `ReconStats::summary`.
+#[cfg(feature = "testing")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReconSummary {
+    /// Number of ciphertexts observed.
+    pub exchanges: u64,
+    /// Smallest and largest safe-bit count seen (both 0 if nothing was observed).
+    pub cnt_min: u16,
+    pub cnt_max: u16,
+    /// Mean safe-bit count.
+    pub cnt_mean: f64,
+    /// `cnt_histogram[c]` ciphertexts had exactly `c` safe bits.
+    pub cnt_histogram: [u32; N + 1],
+    /// `selection[j]` ciphertexts selected position `j`.
+    pub selection: [u32; N],
+}
+
+#[cfg(feature = "testing")]
+impl ReconStats {
+    pub fn new() -> Self {
+        ReconStats { observed: 0, cnt_histogram: [0; N + 1], selected: [0; N] }
+    }
+
+    /// Records one ciphertext. The count is taken from `mask`, so a
+    /// ciphertext with an inconsistent `cnt` is still binned correctly.
+    pub fn observe(&mut self, ct: &MirCiphertext) {
+        self.observed += 1;
+        self.cnt_histogram[ct::popcount(&ct.mask) as usize] += 1;
+
+        let mut idx = 0;
+        while idx < N {
+            self.selected[idx] += ct::bit_get(&ct.mask, idx);
+            idx += 1;
+        }
+    }
+
+    pub fn summary(&self) -> ReconSummary {
+        let mut cnt_min = 0;
+        let mut cnt_max = 0;
+        let mut total: u64 = 0;
+        let mut seen = false;
+
+        let mut c = 0;
+        while c <= N {
+            let hits = self.cnt_histogram[c];
+            if hits > 0 {
+                if !seen {
+                    cnt_min = c as u16;
+                    seen = true;
+                }
+                cnt_max = c as u16;
+                total += c as u64 * hits as u64;
+            }
+            c += 1;
+        }
+
+        let cnt_mean = if self.observed == 0 { 0.0 } else { total as f64 / self.observed as f64 };
+        ReconSummary {
+            exchanges: self.observed,
+            cnt_min,
+            cnt_max,
+            cnt_mean,
+            cnt_histogram: self.cnt_histogram,
+            selection: self.selected,
+        }
+    }
+}
+
+#[cfg(feature = "testing")]
+impl Default for ReconStats {
+    fn default() -> Self {
+        ReconStats::new()
+    }
+}
+
+/// Errors returned by fallible Mirletis operations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MirError {
+    /// An input buffer or field has the wrong length.
+    BadLength,
+    /// An encoding is structurally invalid or non-canonical.
+    Malformed,
+    /// A decoded field lies outside the range its type allows (`cnt > N`).
+    OutOfRange,
+    /// A ciphertext's `cnt` disagrees with the number of bits set in its mask.
+    InconsistentCount,
+    /// Too few bits of entropy would feed the derived key.
+    InsufficientEntropy,
+    /// A freshly generated key pair failed its pairwise consistency test.
+    InconsistentKeypair,
+    /// The random number generator failed to produce output.
+    Rng,
+    /// A checksummed text encoding failed its checksum.
+    BadChecksum,
+    /// The caller's verifier rejected the ciphertext signature.
+    BadSignature,
+    /// A serialized blob carries a format version this build cannot read.
+    UnsupportedVersion,
+    /// A ciphertext's integrity tag does not match (`mac` feature).
+    InvalidCiphertext,
+    /// Reading the input failed for a reason other than ending early (`std` feature).
+    Io,
+}
+
+impl core::fmt::Display for MirError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            MirError::BadLength => "input has the wrong length",
+            MirError::Malformed => "encoding is malformed or not canonical",
+            MirError::OutOfRange => "field value is out of range",
+            MirError::InconsistentCount => "ciphertext count does not match its mask",
+            MirError::InsufficientEntropy => "too few bits of entropy for the derived key",
+            MirError::InconsistentKeypair => "public and secret key do not belong together",
+            MirError::Rng => "random number generator failed",
+            MirError::BadChecksum => "checksum mismatch",
+            MirError::BadSignature => "ciphertext signature rejected",
+            MirError::UnsupportedVersion => "unsupported format version",
+            MirError::InvalidCiphertext => "ciphertext integrity tag mismatch",
+            MirError::Io => "input could not be read",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MirError {}
+
 /* === [3. Secret Vault] === */
 
+/// Backing storage for the secret vector `s` (`K * N` coefficients).
+#[cfg(feature = "alloc")]
+pub type SecretVec = Vec<i16>;
+
+/// Backing storage for the secret vector `s` (`K * N` coefficients).
+#[cfg(not(feature = "alloc"))]
+pub type SecretVec = heapless::Vec<i16, { K * N }>;
+
+/// Returns a zero-filled secret vector of length `K * N`.
+fn zeroed_secret() -> SecretVec {
+    #[cfg(feature = "alloc")]
+    {
+        vec![0i16; K * N]
+    }
+    #[cfg(not(feature = "alloc"))]
+    {
+        let mut s = SecretVec::new();
+        let _ = s.resize(K * N, 0);
+        s
+    }
+}
+
 /// Protected container for the Secret Key component `s`.
 /// Prevents accidental exposure by enforcing closure-based access.
 /// This pattern mitigates potential side-channel leakage by limiting scope.
-#[derive(Zeroize, ZeroizeOnDrop)]
+///
+/// The vault is `Send + Sync`: `access` only takes `&self` and nothing inside
+/// is interiorly mutable (a seed-backed vault re-derives `s` into per-call
+/// scratch). One vault behind an `Arc` can therefore serve concurrent
+/// `decaps` calls from many threads without cloning the secret.
+#[cfg_attr(feature = "alloc", derive(Zeroize, ZeroizeOnDrop))]
 pub struct MirSecretVault {
-    secret_s: Vec<i16>,
+    secret_s: SecretVec,
+    /// Seed `s` was sampled from, when known (kept for backup export).
+    secret_seed: Option<[u8; SECRET_SEED_BYTES]>,
+}
+
+// Fails to compile if a refactor ever adds a non-`Send`/non-`Sync` field.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<MirSecretVault>();
+};
+
+#[cfg(not(feature = "alloc"))]
+impl Zeroize for MirSecretVault {
+    fn zeroize(&mut self) {
+        self.secret_s.as_mut_slice().zeroize();
+        self.secret_s.clear();
+        self.secret_seed.zeroize();
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+impl Drop for MirSecretVault {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+impl ZeroizeOnDrop for MirSecretVault {}
+
+/// Exported 32-byte secret-key seed, as returned by
+/// `MirSecretVault::export_secret_seed`. Zeroized on drop.
+#[derive(Zeroize, ZeroizeOnDrop)]
+pub struct MirSecretSeed {
+    bytes: [u8; SECRET_SEED_BYTES],
+}
+
+impl MirSecretSeed {
+    /// Wraps raw seed bytes, e.g. after decrypting a backup.
+    pub fn from_bytes(bytes: [u8; SECRET_SEED_BYTES]) -> Self {
+        MirSecretSeed { bytes }
+    }
+
+    /// Raw seed bytes. Treat them exactly like the secret key.
+    pub fn as_bytes(&self) -> &[u8; SECRET_SEED_BYTES] {
+        &self.bytes
+    }
 }
 
 impl MirSecretVault {
-    pub fn new(s: Vec<i16>) -> Self {
-        MirSecretVault { secret_s: s }
+    pub fn new(s: SecretVec) -> Self {
+        MirSecretVault { secret_s: s, secret_seed: None }
+    }
+
+    /// Imports `s` produced elsewhere (e.g. by a reference implementation),
+    /// validating it where `new` takes it on trust.
+    ///
+    /// Every coefficient is checked, without stopping at the first bad one,
+    /// so the timing does not point at it. A rejected `s` is zeroized.
+    ///
+    /// # Errors
+    /// `MirError::BadLength` unless `s` has exactly `K * N` coefficients, and
+    /// `MirError::OutOfRange` if any of them is outside `{-1, 0, 1}`.
+    pub fn from_coefficients(s: SecretVec) -> Result<Self, MirError> {
+        // Wrapped first, so every early return scrubs `s` on drop
+        let vault = MirSecretVault::new(s);
+        if vault.secret_s.len() != K * N {
+            return Err(MirError::BadLength);
+        }
+
+        let mut ternary = 1u32;
+        for &c in vault.secret_s.iter() {
+            ternary &= ct::lt32(ct::abs32(c as i32), 2);
+        }
+        if ternary == 0 {
+            return Err(MirError::OutOfRange);
+        }
+        Ok(vault)
+    }
+
+    /// Rebuilds a vault from a seed previously obtained with `export_secret_seed`.
+    pub fn from_secret_seed(seed: &MirSecretSeed) -> Self {
+        let mut vault = MirSecretVault { secret_s: zeroed_secret(), secret_seed: Some(seed.bytes) };
+        gen_secret_from_seed(&seed.bytes, &mut vault.secret_s);
+        vault
+    }
+
+    /// Builds a seed-backed vault that keeps only the 32-byte seed resident.
+    ///
+    /// Every `access` (and so every `decaps`) re-derives `s` into a guarded
+    /// stack buffer and scrubs it as soon as the closure returns, trading one
+    /// SHAKE expansion per call for a much shorter exposure window.
+    pub fn seed_backed(seed: &MirSecretSeed) -> Self {
+        MirSecretVault { secret_s: SecretVec::new(), secret_seed: Some(seed.bytes) }
+    }
+
+    /// Returns `true` if `s` is re-derived on each access (`seed_backed`).
+    pub fn is_seed_backed(&self) -> bool {
+        self.secret_s.is_empty() && self.secret_seed.is_some()
+    }
+
+    /// **Exports the secret key.** Returns the 32-byte seed from which `s` is
+    /// derived, the most compact form for an encrypted backup.
+    ///
+    /// # Warning
+    /// Anyone holding these bytes can decapsulate every ciphertext sent to
+    /// this key pair. Encrypt them before they leave process memory and never
+    /// log them. The wrapper zeroizes itself on drop; copies made through
+    /// `as_bytes` do not.
+    ///
+    /// Returns `None` for vaults built from raw coefficients with `new`,
+    /// which have no seed.
+    pub fn export_secret_seed(&self) -> Option<MirSecretSeed> {
+        self.secret_seed.map(MirSecretSeed::from_bytes)
+    }
+
+    /// Re-derives `s` from a secret-key seed and compares it with the stored
+    /// vector in constant time (`testing` feature, for audits).
+    ///
+    /// `seed` is the input to the secret sampler, i.e. the back half of the
+    /// key-generation master seed. Seeds shorter than `MIN_SEED_LEN` never match.
+    #[cfg(feature = "testing")]
+    pub fn matches_seed(&self, seed: &[u8]) -> bool {
+        if seed.len() < MIN_SEED_LEN {
+            return false;
+        }
+
+        let mut derived = ScratchGuard::new([0i16; K * N]);
+        gen_secret_from_seed(seed, &mut *derived);
+
+        self.access(|stored| {
+            if stored.len() != K * N {
+                return false;
+            }
+            let mut diff = 0u16;
+            let mut i = 0;
+            while i < K * N {
+                diff |= (derived[i] ^ stored[i]) as u16;
+                i += 1;
+            }
+            diff == 0
+        })
     }
 
     /// Access the secret key securely via a closure.
+    ///
+    /// For a seed-backed vault, `s` only exists for the duration of `f`.
     pub fn access<F, R>(&self, f: F) -> R
     where
     F: FnOnce(&[i16]) -> R,
     {
+        if let (true, Some(seed)) = (self.secret_s.is_empty(), &self.secret_seed) {
+            let mut s = ScratchGuard::new([0i16; K * N]);
+            gen_secret_from_seed(seed, &mut *s);
+            return f(&*s);
+        }
         f(&self.secret_s)
     }
 }
 
+/// Internal RAII wrapper for secret-derived scratch buffers.
+///
+/// Zeroizes its contents on drop, so an early return or a future edit can
+/// never leave a buffer un-scrubbed.
+///
+/// Every secret-derived intermediate lives in one of these: the master and
+/// ephemeral seeds, the SHAKE output chunks feeding `s` / `r`, the ephemeral
+/// `r` itself, and the products `v = b * r` and `v' = u * s`. The secret `s`
+/// is derived straight into vault storage. The only state not scrubbed is the
+/// internal sponge of the `sha3` hashers/readers, which that crate does not
+/// expose for zeroization.
+struct ScratchGuard<T: Zeroize>(T);
+
+impl<T: Zeroize> ScratchGuard<T> {
+    #[inline(always)]
+    fn new(inner: T) -> Self {
+        ScratchGuard(inner)
+    }
+}
+
+impl<T: Zeroize> core::ops::Deref for ScratchGuard<T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> core::ops::DerefMut for ScratchGuard<T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Zeroize> Drop for ScratchGuard<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
 /* === [4. Branchless Primitives] === */
 /// Constant-time operations to prevent timing attacks.
+///
+/// Domain contract: `min32` and `lt32` compute `a - b` and are exact only
+/// when that difference fits in an `i32`. `abs32` is total: it saturates
+/// `i32::MIN` to `i32::MAX`. All call sites stay far inside these bounds
+/// (`safe_zone` distances are at most 255).
 mod ct {
+    /// Returns -1 if `x` is negative, else 0 (arithmetic shift).
     #[inline(always)]
     pub const fn sign32(x: i32) -> i32 {
         x >> 31
     }
 
+    /// Saturating absolute value. The two's-complement negation only wraps
+    /// for `i32::MIN`, the one result that comes out negative; flipping its
+    /// bits with its own sign turns it into `i32::MAX` and leaves every
+    /// non-negative result alone.
     #[inline(always)]
     pub const fn abs32(x: i32) -> i32 {
         let m = sign32(x);
-        (x ^ m).wrapping_sub(m)
+        let t = (x ^ m).wrapping_sub(m);
+        t ^ sign32(t)
     }
 
     #[inline(always)]
@@ -126,12 +952,35 @@ mod ct {
         (a & mask) | (b & !mask)
     }
 
+    #[allow(dead_code)] // Part of the primitive set (mirrors MIR_SEL in C).
     #[inline(always)]
     pub const fn sel_i16(a: i16, b: i16, cond: u32) -> i16 {
         let mask = 0i16.wrapping_sub(cond as i16);
         (a & mask) | (b & !mask)
     }
 
+    #[inline(always)]
+    pub const fn sel_u16(a: u16, b: u16, cond: u32) -> u16 {
+        let mask = 0u16.wrapping_sub(cond as u16);
+        (a & mask) | (b & !mask)
+    }
+
+    /// Returns 1 if `a` sorts before `b` (lexicographic, equal lengths), else 0.
+    ///
+    /// Scans from the last byte to the first so the first differing byte
+    /// decides, without an early exit.
+    #[inline]
+    pub fn lt_slice(a: &[u8], b: &[u8]) -> u32 {
+        let mut lt = 0u32;
+        let mut i = a.len();
+        while i > 0 {
+            i -= 1;
+            let eq = eq32(a[i] as i32, b[i] as i32);
+            lt = (lt & eq.wrapping_neg()) | (lt32(a[i] as i32, b[i] as i32) & !eq.wrapping_neg());
+        }
+        lt
+    }
+
     #[inline(always)]
     pub fn bit_set(arr: &mut [u8], i: usize, v: u32) {
         arr[i >> 3] |= (v as u8) << (i & 7);
@@ -143,24 +992,105 @@ mod ct {
     }
 
     /// Constant-time ternary sampler: {0,1,2,3} -> {-1,0,1,0}
+    ///
+    /// Bit 1 picks the sign (`2 * b1 - 1`) and bit 0 masks it out
+    /// (`b0 - 1` is all ones when clear, zero when set), so the mapping is
+    /// pure arithmetic with no comparison at all.
+    ///
+    /// Zero is produced for both 1 and 3 on purpose: over uniform bytes this
+    /// gives P(0) = 1/2 and P(-1) = P(+1) = 1/4, i.e. a centered binomial
+    /// distribution with eta = 1. It has mean zero and keeps the rounding
+    /// noise in `v - v'` small.
     #[inline(always)]
     pub const fn ternary(r: u8) -> i16 {
-        let val = (r & 3) as i32;
-        let base = val - 1;
-        let is_three = eq32(val, 3);
-        sel_i16(0, base as i16, is_three)
+        let sign = (((r >> 1) & 1) as i16) * 2 - 1;
+        let keep = ((r & 1) as i16) - 1;
+        sign & keep
+    }
+
+    /// Safe-Zone centers in `Z_p` (p = 256). One per quarter of the ring,
+    /// placed in the middle of each run of constant `recon_bit(v)`.
+    pub const ZONE_CENTERS: [i32; 4] = [32, 96, 160, 224];
+
+    /// A value is safe if its distance to the nearest center is below this.
+    pub const ZONE_THRESHOLD: i32 = 12;
+
+    /// Worst-case `|v - v'|` in `Z_p` for an honest exchange with `k` blocks.
+    ///
+    /// The rounding errors contribute at most `2 * k * (2^SHIFT - 1)` in `Z_q`.
+    /// A prime `q` adds one wrap term per block on each side (`|floor(acc / q)|
+    /// <= k`), at most `2 * k * k` more. Scaled down by `2^SHIFT`.
+    #[cfg(not(feature = "prime-modulus"))]
+    pub const fn zone_max_drift(k: usize) -> i32 {
+        (2 * k as i32 * ((1 << super::SHIFT) - 1)) >> super::SHIFT
+    }
+    /// Worst-case `|v - v'|` in `Z_p` for an honest exchange with `k` blocks.
+    #[cfg(feature = "prime-modulus")]
+    pub const fn zone_max_drift(k: usize) -> i32 {
+        (2 * k as i32 * ((1 << super::SHIFT) - 1) + 2 * (k * k) as i32) >> super::SHIFT
+    }
+
+    /// `zone_max_drift` for this build's `K`.
+    pub const ZONE_MAX_DRIFT: i32 = zone_max_drift(super::K);
+
+    /// Width of each run of constant `recon_bit(v)`.
+    const RUN: i32 = 1 << super::RECON_BIT;
+
+    const fn centered(centers: &[i32]) -> bool {
+        let mut i = 0;
+        while i < centers.len() {
+            if centers[i] % RUN != RUN / 2 {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+
+    // Moving `RECON_BIT` without moving the centers would make the two sides
+    // disagree near run edges, so the pairing is checked at compile time.
+    const _: () = assert!(centered(&ZONE_CENTERS), "zone centers must sit mid-run of RECON_BIT");
+
+    // `p` is 256 in both modes, so the centers carry over unchanged; the
+    // threshold only has to keep a drifted `v'` inside its run.
+    const _: () = assert!(ZONE_THRESHOLD + ZONE_MAX_DRIFT <= RUN / 2, "safe zone too wide for the noise bound");
+
+    /// The reconciled key bit of `v` (position `RECON_BIT`).
+    #[inline(always)]
+    pub const fn recon_bit(v: u8) -> u8 {
+        (v >> super::RECON_BIT) & 1
     }
 
-    /// Safe-Zone Logic: Returns 1 if distance < 12, else 0.
+    /// Safe-Zone Logic: Returns 1 if distance < `ZONE_THRESHOLD`, else 0.
     #[inline(always)]
     pub fn safe_zone(v: u8) -> u32 {
         let val = v as i32;
-        let d1 = abs32(val - 32);
-        let d2 = abs32(val - 96);
-        let d3 = abs32(val - 160);
-        let d4 = abs32(val - 224);
-        let m = min32(min32(d1, d2), min32(d3, d4));
-        lt32(m, 12)
+        let mut m = i32::MAX;
+        let mut i = 0;
+        while i < ZONE_CENTERS.len() {
+            m = min32(m, abs32(val - ZONE_CENTERS[i]));
+            i += 1;
+        }
+        lt32(m, ZONE_THRESHOLD)
+    }
+
+    /// Constant-time population count of a byte slice.
+    ///
+    /// Each byte is reduced with the usual SWAR steps (pairs, nibbles, byte),
+    /// so the work depends only on `arr.len()`, never on the bits themselves.
+    #[inline(always)]
+    pub const fn popcount(arr: &[u8]) -> u16 {
+        let mut total = 0u16;
+        let mut i = 0;
+        while i < arr.len() {
+            let mut x = arr[i];
+            x = x - ((x >> 1) & 0x55);
+            x = (x & 0x33) + ((x >> 2) & 0x33);
+            x = (x + (x >> 4)) & 0x0F;
+            total += x as u16;
+            i += 1;
+        }
+        total
     }
 
     /// Constant-time slice comparison.
@@ -177,293 +1107,2291 @@ mod ct {
         }
         diff == 0
     }
-}
 
-/* === [5. SHAKE-256 / SHA3-256 Engine] === */
+    #[cfg(test)]
+    mod tests {
+        use super::*;
 
-fn mir_shake_xof(out: &mut [u8], data: &[u8], domain: u8) {
-    let mut hasher = Shake256::default();
-    hasher.update(&[domain]);
-    hasher.update(data);
-    let mut reader = hasher.finalize_xof();
-    reader.read(out);
-}
+        const EDGES: [i32; 9] = [i32::MIN, i32::MIN + 1, -256, -1, 0, 1, 256, i32::MAX - 1, i32::MAX];
 
-fn mir_sha3_256(out: &mut [u8; 32], data: &[u8], domain: u8) {
-    let mut hasher = Sha3_256::new();
-    Digest::update(&mut hasher, &[domain]);
-    Digest::update(&mut hasher, data);
-    out.copy_from_slice(&hasher.finalize());
+        #[test]
+        fn test_sign32() {
+            for &x in EDGES.iter() {
+                assert_eq!(sign32(x), if x < 0 { -1 } else { 0 }, "x = {}", x);
+            }
+        }
+
+        #[test]
+        fn test_abs32() {
+            for &x in EDGES[1..].iter() {
+                assert_eq!(abs32(x), x.abs(), "x = {}", x);
+            }
+            // Regression: the only input without an i32 absolute value saturates.
+            assert_eq!(abs32(i32::MIN), i32::MAX);
+            assert_eq!(abs32(i32::MIN), i32::MIN.saturating_abs());
+        }
+
+        #[test]
+        fn test_min32_lt32_in_domain() {
+            for &a in EDGES.iter() {
+                for &b in EDGES.iter() {
+                    if a.checked_sub(b).is_none() {
+                        continue;
+                    }
+                    assert_eq!(min32(a, b), a.min(b), "min32({}, {})", a, b);
+                    assert_eq!(lt32(a, b), (a < b) as u32, "lt32({}, {})", a, b);
+                }
+            }
+        }
+
+        #[test]
+        fn test_eq32_full_range() {
+            for &a in EDGES.iter() {
+                for &b in EDGES.iter() {
+                    assert_eq!(eq32(a, b), (a == b) as u32, "eq32({}, {})", a, b);
+                }
+            }
+        }
+
+        #[test]
+        fn test_select_exhaustive() {
+            for a in 0..=255u8 {
+                for b in 0..=255u8 {
+                    assert_eq!(sel_u8(a, b, 1), a);
+                    assert_eq!(sel_u8(a, b, 0), b);
+                }
+            }
+            for &a in [i16::MIN, -1, 0, 1, i16::MAX].iter() {
+                for &b in [i16::MIN, -1, 0, 1, i16::MAX].iter() {
+                    assert_eq!(sel_i16(a, b, 1), a);
+                    assert_eq!(sel_i16(a, b, 0), b);
+                }
+            }
+            for &a in [0, 1, 0x7FFF, 0x8000, u16::MAX].iter() {
+                for &b in [0, 1, 0x7FFF, 0x8000, u16::MAX].iter() {
+                    assert_eq!(sel_u16(a, b, 1), a);
+                    assert_eq!(sel_u16(a, b, 0), b);
+                }
+            }
+        }
+
+        #[test]
+        fn test_lt_slice() {
+            let cases: [([u8; 3], [u8; 3]); 5] = [
+                ([0, 0, 0], [0, 0, 0]),
+                ([0, 0, 1], [0, 0, 2]),
+                ([1, 0, 0], [0, 9, 9]),
+                ([0, 1, 0], [0, 1, 255]),
+                ([255; 3], [254, 255, 255]),
+            ];
+            for (a, b) in cases.iter() {
+                assert_eq!(lt_slice(a, b), (a < b) as u32);
+                assert_eq!(lt_slice(b, a), (b < a) as u32);
+            }
+        }
+
+        #[test]
+        fn test_popcount() {
+            assert_eq!(popcount(&[]), 0);
+            assert_eq!(popcount(&[0x00; 32]), 0);
+            assert_eq!(popcount(&[0xFF; 32]), 256);
+            assert_eq!(popcount(&[0x55, 0xAA, 0x0F, 0xF0]), 16);
+            assert_eq!(popcount(&[0x01, 0x80, 0x10, 0x00]), 3);
+            for b in 0..=255u8 {
+                assert_eq!(popcount(&[b]), b.count_ones() as u16, "b = {:#04x}", b);
+            }
+        }
+
+        #[test]
+        fn test_ternary_exhaustive() {
+            let mut counts = [0u32; 3];
+            for r in 0..=255u8 {
+                let expected = [-1, 0, 1, 0][(r & 3) as usize];
+                let t = ternary(r);
+                assert_eq!(t, expected, "r = {}", r);
+                counts[(t + 1) as usize] += 1;
+            }
+            // Centered binomial (eta = 1): 1/4, 1/2, 1/4.
+            assert_eq!(counts, [64, 128, 64]);
+        }
+
+        #[test]
+        fn test_safe_zone_exhaustive() {
+            for v in 0..=255u8 {
+                let m = ZONE_CENTERS.iter().map(|&c| (v as i32 - c).abs()).min().unwrap();
+                assert_eq!(safe_zone(v), (m < ZONE_THRESHOLD) as u32, "v = {}", v);
+            }
+        }
+
+        #[test]
+        fn test_recon_bit_constant_within_drift() {
+            for &c in ZONE_CENTERS.iter() {
+                let reach = ZONE_THRESHOLD + ZONE_MAX_DRIFT;
+                for v in c - reach..c + reach {
+                    assert_eq!(recon_bit(v as u8), recon_bit(c as u8), "v = {}", v);
+                }
+            }
+        }
+    }
 }
 
-/* === [6. Matrix & Secret Generation] === */
+/* === [5. SHAKE-256 / SHA3-256 Engine] === */
+
+fn mir_shake_reader(data: &[u8], domain: u8) -> Shake256Reader {
+    let mut hasher = Shake256::default();
+    hasher.update(&[domain]);
+    hasher.update(data);
+    hasher.finalize_xof()
+}
 
-fn gen_matrix_a(seed: &[u8; SEED_LEN]) -> Vec<i16> {
-    let len = K * K * N;
-    let mut buf = vec![0u8; len * 2];
-    let mut matrix = vec![0i16; len];
+fn mir_sha3_256(out: &mut [u8; 32], parts: &[&[u8]], domain: u8) {
+    let mut hasher = Sha3_256::new();
+    Digest::update(&mut hasher, [domain]);
+    for part in parts {
+        Digest::update(&mut hasher, part);
+    }
+    out.copy_from_slice(&hasher.finalize());
+}
 
-    mir_shake_xof(&mut buf, seed, DOM_MATRIX);
+/// KDF over the reconciled bits, shared by `encaps` and `decaps`.
+///
+/// Absorbs `domains::HASH || out.len() (8-byte LE) || cnt (2-byte LE) ||
+/// bit_0 || ... || bit_{cnt-1}` into SHAKE-256 and squeezes `out.len()` bytes,
+/// where the bits are `ct::recon_bit(v[idx])` for every `idx` selected by `mask`,
+/// in ascending order. Because the length is absorbed first, outputs of
+/// different lengths are unrelated rather than prefixes of one another.
+///
+/// A non-empty `context` (see `MirConfig`) switches the tag to
+/// `domains::HASH_CTX` and absorbs `context.len() (8-byte LE) || context`
+/// right after the output length. The empty context keeps the layout above,
+/// so the default configuration is byte-identical to the free functions.
+///
+/// The bits are fed to the sponge as they are found, so no reconciliation
+/// buffer is ever materialized. The selection branches only on `mask`, which
+/// is public (it travels in the ciphertext).
+#[cfg(not(feature = "padded-kdf"))]
+fn derive_shared_bytes(v: &[u8; N], mask: &[u8; N / 8], cnt: u16, context: &[u8], out: &mut [u8]) {
+    let mut hasher = Shake256::default();
+    if context.is_empty() {
+        hasher.update(&[domains::HASH]);
+        hasher.update(&(out.len() as u64).to_le_bytes());
+    } else {
+        hasher.update(&[domains::HASH_CTX]);
+        hasher.update(&(out.len() as u64).to_le_bytes());
+        hasher.update(&(context.len() as u64).to_le_bytes());
+        hasher.update(context);
+    }
+    hasher.update(&cnt.to_le_bytes());
 
     let mut idx = 0;
-    let mut i = 0;
-    while i < len {
-        let val = u16::from_le_bytes([buf[idx], buf[idx + 1]]);
-        matrix[i] = (val as i32 & Q_MASK) as i16;
-        idx += 2;
-        i += 1;
+    while idx < N {
+        if ct::bit_get(mask, idx) == 1 {
+            hasher.update(&[ct::recon_bit(v[idx])]);
+        }
+        idx += 1;
     }
 
-    buf.zeroize();
-    matrix
+    hasher.finalize_xof().read(out);
 }
 
-fn gen_secret_from_seed(seed: &[u8], out_s: &mut [i16]) {
-    let len = out_s.len();
-    let mut buf = vec![0u8; len];
+/// Fixed-length KDF over the reconciled bits (`padded-kdf` feature).
+///
+/// Absorbs `domains::HASH_PADDED || out.len() (8-byte LE) || context.len()
+/// (8-byte LE) || context || cnt (2-byte LE) || mask || bits`, where `bits`
+/// is always `N` bytes: `ct::recon_bit(v[idx])` where `mask` selects `idx`,
+/// and zero elsewhere. For a given context the sponge input is therefore
+/// always the same length, whatever `cnt` is, which suits fixed-length
+/// hardware hash cores. `mask` is absorbed so that a selected zero bit and
+/// an unselected position still hash differently.
+///
+/// The trade-off against the default KDF: the input is `N + N / 8 + 19`
+/// bytes (three SHAKE-256 blocks without context) instead of `11 + cnt`
+/// (usually one), and the keys differ, so both sides must be built with the
+/// feature. `FORMAT_VERSION` marks the build, so blobs from the other mode
+/// are rejected instead of decapsulating to an unrelated key. The default
+/// KDF only leaks `cnt` through its input length, and `cnt` is public.
+#[cfg(feature = "padded-kdf")]
+fn derive_shared_bytes(v: &[u8; N], mask: &[u8; N / 8], cnt: u16, context: &[u8], out: &mut [u8]) {
+    let mut bits = ScratchGuard::new([0u8; N]);
+    let mut idx = 0;
+    while idx < N {
+        bits[idx] = ct::recon_bit(v[idx]) & ct::bit_get(mask, idx) as u8;
+        idx += 1;
+    }
 
-    mir_shake_xof(&mut buf, seed, DOM_SECRET);
+    let mut hasher = Shake256::default();
+    hasher.update(&[domains::HASH_PADDED]);
+    hasher.update(&(out.len() as u64).to_le_bytes());
+    hasher.update(&(context.len() as u64).to_le_bytes());
+    hasher.update(context);
+    hasher.update(&cnt.to_le_bytes());
+    hasher.update(mask);
+    hasher.update(&*bits);
 
-    let mut i = 0;
-    while i < len {
-        out_s[i] = ct::ternary(buf[i]);
-        i += 1;
+    hasher.finalize_xof().read(out);
+}
+
+/// AES-256-CTR keystream keyed by the matrix seed, used as a XOF
+/// (`poly::AesCtrXof`).
+///
+/// The counter block starts as `domains::MATRIX_AES || 0^15`, so the stream is
+/// domain-separated from (and never equal to) the SHAKE expansion.
+#[cfg(feature = "aes-expand")]
+pub struct AesCtrReader(ctr::Ctr128BE<aes::Aes256>);
+
+#[cfg(feature = "aes-expand")]
+impl AesCtrReader {
+    fn new(seed: &[u8; SEED_LEN]) -> Self {
+        use aes::cipher::KeyIvInit;
+        let mut iv = [0u8; 16];
+        iv[0] = domains::MATRIX_AES;
+        AesCtrReader(ctr::Ctr128BE::<aes::Aes256>::new(seed.into(), &iv.into()))
     }
+}
 
-    buf.zeroize();
+#[cfg(feature = "aes-expand")]
+impl XofReader for AesCtrReader {
+    fn read(&mut self, buffer: &mut [u8]) {
+        use aes::cipher::StreamCipher;
+        buffer.fill(0);
+        self.0.apply_keystream(buffer);
+    }
 }
 
-/* === [7. Key Generation] === */
+/* === [6. Randomness] === */
 
-/// Generates a new Mirletis key pair.
+/// Default entropy source used by `keygen()` and `encaps()`.
 ///
-/// # Returns
-/// A tuple containing (`MirPubkey`, `MirSecretVault`).
-pub fn keygen() -> (MirPubkey, MirSecretVault) {
-    let mut pk = MirPubkey::default();
-
-    // 1. Generate two independent seeds from OS RNG
-    let mut master_seed = [0u8; 64];
-    OsRng.fill_bytes(&mut master_seed);
+/// `OsRng` on native targets; the `getrandom` adapter on `wasm32` when the
+/// `wasm` feature is enabled.
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub type DefaultRng = rand::rngs::OsRng;
 
-    // 2. Public Key Seed (Front 32 bytes)
-    pk.seed.copy_from_slice(&master_seed[..32]);
+/// Default entropy source used by `keygen()` and `encaps()`.
+///
+/// `OsRng` on native targets; the `getrandom` adapter on `wasm32` when the
+/// `wasm` feature is enabled.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub type DefaultRng = GetrandomRng;
 
-    // 3. Secret Key Seed (Back 32 bytes) - Independent derivation
-    let mut s_temp = vec![0i16; K * N];
-    gen_secret_from_seed(&master_seed[32..], &mut s_temp);
+/// CSPRNG adapter over `getrandom::getrandom`.
+/// On `wasm32-unknown-unknown` this draws from `crypto.getRandomValues`.
+#[cfg(feature = "wasm")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GetrandomRng;
 
-    // 4. Zeroize master seed immediately
-    master_seed.zeroize();
+#[cfg(feature = "wasm")]
+impl RngCore for GetrandomRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut buf = [0u8; 4];
+        self.fill_bytes(&mut buf);
+        u32::from_le_bytes(buf)
+    }
 
-    // 5. Generate Matrix A from seed
-    let matrix_a = gen_matrix_a(&pk.seed);
+    fn next_u64(&mut self) -> u64 {
+        let mut buf = [0u8; 8];
+        self.fill_bytes(&mut buf);
+        u64::from_le_bytes(buf)
+    }
 
-    // 6. Compute b = A * s (Component-wise / Parallel)
-    // Note: Uses branchless wrapping arithmetic.
-    let mut i = 0;
-    while i < K {
-        let mut j = 0;
-        while j < N {
-            let mut acc: i32 = 0;
-            let mut l = 0;
-            while l < K {
-                let idx_a = (i * K * N) + (l * N) + j;
-                let idx_s = (l * N) + j;
-                let term = (matrix_a[idx_a] as i32).wrapping_mul(s_temp[idx_s] as i32);
-                acc = acc.wrapping_add(term);
-                l += 1;
-            }
-            pk.b[i * N + j] = ((acc & Q_MASK) >> SHIFT) as u8;
-            j += 1;
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        if let Err(e) = self.try_fill_bytes(dest) {
+            panic!("Error: {}", e);
         }
-        i += 1;
     }
 
-    // 7. Transfer secret to the secure Vault
-    let vault = MirSecretVault::new(s_temp);
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        getrandom::getrandom(dest).map_err(rand::Error::from)
+    }
+}
 
-    (pk, vault)
+#[cfg(feature = "wasm")]
+impl CryptoRng for GetrandomRng {}
+
+/// Fills `dest` from `rng`, reporting failure instead of panicking.
+fn try_fill<R: RngCore>(rng: &mut R, dest: &mut [u8]) -> Result<(), MirError> {
+    rng.try_fill_bytes(dest).map_err(|_| MirError::Rng)
 }
 
-/* === [8. Encapsulation] === */
+/* === [7. Polynomial Arithmetic] === */
 
-/// Encapsulates a shared secret for the given public key.
+/// Public lattice arithmetic shared by the KEM.
 ///
-/// # Arguments
-/// * `pk` - The recipient's Public Key.
+/// Vectors are laid out as `K` consecutive blocks of `N` coefficients and the
+/// matrix `A` as `K * K` blocks, with block `(i, l)` at offset `(i * K + l) * N`.
+/// All products are component-wise within a block and use branchless wrapping
+/// arithmetic, so they are safe to call on secret operands.
 ///
-/// # Returns
-/// A tuple containing (`MirCiphertext`, `MirSharedKey`).
-pub fn encaps(pk: &MirPubkey) -> (MirCiphertext, MirSharedKey) {
-    // Generate ephemeral entropy
-    let mut eph_seed = [0u8; 32];
-    OsRng.fill_bytes(&mut eph_seed);
+/// # Panics
+/// The products panic if a slice is shorter than the layout above requires.
+pub mod poly {
+    use super::*;
 
-    let mut r = vec![0i16; K * N];
-    gen_secret_from_seed(&eph_seed, &mut r);
-    eph_seed.zeroize();
+    /// Reduces an accumulator modulo `q`.
+    #[cfg(not(feature = "prime-modulus"))]
+    #[inline(always)]
+    pub const fn reduce(x: i32) -> i32 {
+        x & Q_MASK
+    }
 
-    let matrix_a = gen_matrix_a(&pk.seed);
+    /// Reduces an accumulator modulo `q = 2^13 - 1`, branch-free.
+    ///
+    /// Adds a multiple of `q` to make `x` non-negative, folds twice using
+    /// `2^13 = 1 (mod q)` and finishes with one masked subtraction. Exact for
+    /// `x >= -(q << 8)`, far beyond any accumulator (`|acc| <= k * q`, `k <= 10`).
+    #[cfg(feature = "prime-modulus")]
+    #[inline(always)]
+    pub const fn reduce(x: i32) -> i32 {
+        let y = x.wrapping_add(Q << 8);
+        let y = (y & Q_MASK) + (y >> 13);
+        let y = (y & Q_MASK) + (y >> 13);
+        let d = y - Q;
+        d + (Q & (d >> 31))
+    }
 
-    let mut ct = MirCiphertext {
-        u: [0u8; K * N],
-        mask: [0u8; N / 8],
-        cnt: 0,
-    };
+    /// Reduces an accumulator modulo `q` and rounds it down to `p = 2^13 >> SHIFT`.
+    #[inline(always)]
+    pub const fn round(x: i32) -> u8 {
+        (reduce(x) >> SHIFT) as u8
+    }
 
-    // Compute u = A^T * r
-    let mut i = 0;
-    while i < K {
-        let mut j = 0;
-        while j < N {
-            let mut acc: i32 = 0;
-            let mut l = 0;
-            while l < K {
-                let idx_a = (l * K * N) + (i * N) + j;
-                let idx_r = (l * N) + j;
-                let term = (matrix_a[idx_a] as i32).wrapping_mul(r[idx_r] as i32);
-                acc = acc.wrapping_add(term);
-                l += 1;
+    /// Reduces an accumulator modulo `p`.
+    #[inline(always)]
+    pub const fn reduce_p(x: i32) -> u8 {
+        (x & 0xFF) as u8
+    }
+
+    /// Storage for the expanded matrix `A` (`K * K * N` coefficients).
+    #[cfg(feature = "alloc")]
+    pub type Matrix = Vec<i16>;
+
+    /// Storage for the expanded matrix `A` (`K * K * N` coefficients).
+    #[cfg(not(feature = "alloc"))]
+    pub type Matrix = [i16; K * K * N];
+
+    /// Expands the public matrix `A` (`K * K * N` coefficients mod `q`) from a seed.
+    pub fn gen_matrix(seed: &[u8; SEED_LEN]) -> Matrix {
+        gen_matrix_with::<DefaultXof>(seed)
+    }
+
+    /// `gen_matrix` with an explicit expander.
+    pub fn gen_matrix_with<X: MatrixXof>(seed: &[u8; SEED_LEN]) -> Matrix {
+        #[cfg(feature = "alloc")]
+        let mut matrix = vec![0i16; K * K * N];
+        #[cfg(not(feature = "alloc"))]
+        let mut matrix = [0i16; K * K * N];
+
+        MatrixStream::<X>::with_xof(seed).fill(&mut matrix);
+        matrix
+    }
+
+    /// Expands the leading `out.len()` coefficients of `A` into `out`.
+    pub fn expand_matrix(seed: &[u8; SEED_LEN], out: &mut [i16]) {
+        MatrixStream::new(seed).fill(out);
+    }
+
+    pub use sha3::digest::XofReader;
+
+    /// A seeded XOF that `A` is sampled from.
+    ///
+    /// `Shake256Xof` is the default. Other expanders plug in through
+    /// `gen_matrix_with` and `MatrixStream::with_xof`, e.g. to benchmark an
+    /// alternative and feed the result to `encaps_with_matrix`. The secret
+    /// sampler is not affected.
+    ///
+    /// The expander defines `A`, so the same seed gives a different public
+    /// key under each one. Keys and ciphertexts are not cross-compatible;
+    /// both sides must expand with the same XOF.
+    pub trait MatrixXof {
+        /// The output stream for one seed.
+        type Reader: XofReader;
+
+        /// Starts the output stream for `seed`.
+        fn reader(seed: &[u8; SEED_LEN]) -> Self::Reader;
+
+        /// Writes the first `out.len()` bytes of the stream for `seed`.
+        fn expand(seed: &[u8; SEED_LEN], out: &mut [u8]) {
+            Self::reader(seed).read(out);
+        }
+    }
+
+    /// `SHAKE256(MATRIX || seed)`, the default expander.
+    pub struct Shake256Xof;
+
+    impl MatrixXof for Shake256Xof {
+        type Reader = Shake256Reader;
+
+        fn reader(seed: &[u8; SEED_LEN]) -> Self::Reader {
+            mir_shake_reader(seed, domains::MATRIX)
+        }
+    }
+
+    /// AES-256-CTR keyed by the seed, for AES-NI targets (`aes-expand` feature).
+    #[cfg(feature = "aes-expand")]
+    pub struct AesCtrXof;
+
+    #[cfg(feature = "aes-expand")]
+    impl MatrixXof for AesCtrXof {
+        type Reader = AesCtrReader;
+
+        fn reader(seed: &[u8; SEED_LEN]) -> Self::Reader {
+            AesCtrReader::new(seed)
+        }
+    }
+
+    /// The expander behind `gen_matrix` and every key operation of this build.
+    #[cfg(not(feature = "aes-expand"))]
+    pub type DefaultXof = Shake256Xof;
+    /// The expander behind `gen_matrix` and every key operation of this build.
+    #[cfg(feature = "aes-expand")]
+    pub type DefaultXof = AesCtrXof;
+
+    /// Incremental expansion of `A`, in the same order `gen_matrix` lays it out.
+    ///
+    /// The XOF stream is consumed one block at a time, so no byte buffer of
+    /// the full matrix size is ever materialized. The stream comes from `X`,
+    /// this build's `DefaultXof` unless chosen with `with_xof`.
+    ///
+    /// Each coefficient is a 13-bit sample, rejected if it is not below `q`
+    /// (never for `q = 2^13`; only `2^13 - 1` itself with `prime-modulus`),
+    /// so the output is uniform mod `q` for either modulus. A rejection just
+    /// pulls more of the stream through the refill buffer; nothing assumes a
+    /// fixed number of XOF bytes per matrix. The branch is on public data.
+    pub struct MatrixStream<X: MatrixXof = DefaultXof> {
+        reader: X::Reader,
+        buf: ScratchGuard<[u8; 2 * N]>,
+        rejected: usize,
+    }
+
+    impl MatrixStream {
+        /// Starts the expansion of `A` for `seed`.
+        pub fn new(seed: &[u8; SEED_LEN]) -> Self {
+            MatrixStream::with_xof(seed)
+        }
+    }
+
+    impl<X: MatrixXof> MatrixStream<X> {
+        /// Starts the expansion of `A` for `seed` with expander `X`.
+        pub fn with_xof(seed: &[u8; SEED_LEN]) -> Self {
+            MatrixStream { reader: X::reader(seed), buf: ScratchGuard::new([0u8; 2 * N]), rejected: 0 }
+        }
+
+        /// Writes the next `out.len()` coefficients of `A` into `out`.
+        pub fn fill(&mut self, out: &mut [i16]) {
+            let len = out.len();
+            let mut i = 0;
+            while i < len {
+                let take = if len - i < N { len - i } else { N };
+                self.reader.read(&mut self.buf[..take * 2]);
+
+                let mut j = 0;
+                while j < take {
+                    let val = (u16::from_le_bytes([self.buf[2 * j], self.buf[2 * j + 1]]) as i32) & Q_MASK;
+                    if val < Q {
+                        out[i] = val as i16;
+                        i += 1;
+                    } else {
+                        self.rejected += 1;
+                    }
+                    j += 1;
+                }
             }
-            ct.u[i * N + j] = ((acc & Q_MASK) >> SHIFT) as u8;
-            j += 1;
         }
-        i += 1;
+
+        /// Number of samples rejected so far.
+        pub fn rejected(&self) -> usize {
+            self.rejected
+        }
     }
 
-    // Compute v = b * r
-    let mut v = [0u8; N];
-    let mut j = 0;
-    while j < N {
+    /// The one accumulation every product shares:
+    /// `sum_l x[block(l) * N + j] * y[l * N + j]`, over the `y.len() / N`
+    /// blocks of `y`.
+    ///
+    /// `block` maps the summation index to the block of `x` it reads, which
+    /// is the only thing that differs between the products. The products
+    /// take the block count from their inputs rather than from `K`, so the
+    /// const-generic parameter sets (`MirPubkeyG`) share them.
+    ///
+    /// `i32` is exact here, not merely correct modulo `2^32`: `y` is always
+    /// ternary and `x` is below `q` (matrix) or `p` (`b`, `u`), so
+    /// `|acc| <= k * (q - 1)`. Even at the largest rank the safe zone admits
+    /// (`k = 10`) that is below `2^17`, inside the exact range of either
+    /// `reduce`. The wrapping operations never wrap for such inputs; they
+    /// only keep the kernel free of overflow checks.
+    #[inline(always)]
+    fn column_acc<T: Copy + Into<i32>>(
+        x: &[T],
+        block: impl Fn(usize) -> usize,
+        y: &[i16],
+        j: usize,
+    ) -> Result<i32, MirError> {
+        let k = y.len() / N;
         let mut acc: i32 = 0;
         let mut l = 0;
-        while l < K {
-            let idx_b = (l * N) + j;
-            let idx_r = (l * N) + j;
-            let term = (pk.b[idx_b] as i32).wrapping_mul(r[idx_r] as i32);
+        while l < k {
+            let term = at(x, block(l) * N + j)?.into().wrapping_mul(at(y, l * N + j)? as i32);
             acc = acc.wrapping_add(term);
             l += 1;
         }
-        v[j] = (acc & 0xFF) as u8;
-        j += 1;
+        Ok(acc)
     }
 
-    // Safe-Zone Selection and Mask Generation
-    let mut buf = [0u8; N];
-    let mut widx: usize = 0;
+    /// Indexed load used by every kernel.
+    ///
+    /// With `debug-bounds` an out-of-range index is returned as
+    /// `MirError::BadLength`, so shape bugs in the manual index math surface
+    /// from the fallible entry points instead of panicking. Otherwise this is
+    /// plain indexing and the `Result` folds away.
+    #[cfg(feature = "debug-bounds")]
+    #[inline(always)]
+    fn at<T: Copy>(x: &[T], i: usize) -> Result<T, MirError> {
+        x.get(i).copied().ok_or(MirError::BadLength)
+    }
+    #[cfg(not(feature = "debug-bounds"))]
+    #[inline(always)]
+    fn at<T: Copy>(x: &[T], i: usize) -> Result<T, MirError> {
+        Ok(x[i])
+    }
 
-    let mut idx = 0;
-    while idx < N {
-        let val = v[idx];
-        let safe = ct::safe_zone(val);
+    /// Indexed store, checked like `at`.
+    #[cfg(feature = "debug-bounds")]
+    #[inline(always)]
+    fn put<T>(out: &mut [T], i: usize, v: T) -> Result<(), MirError> {
+        *out.get_mut(i).ok_or(MirError::BadLength)? = v;
+        Ok(())
+    }
+    #[cfg(not(feature = "debug-bounds"))]
+    #[inline(always)]
+    fn put<T>(out: &mut [T], i: usize, v: T) -> Result<(), MirError> {
+        out[i] = v;
+        Ok(())
+    }
 
-        ct::bit_set(&mut ct.mask, idx, safe);
-        // Extract 1 bit from the safe zone
-        let bit = (val >> 6) & 1;
-        buf[widx] = ct::sel_u8(bit, buf[widx], safe);
-        widx = widx.wrapping_add(safe as usize);
+    /// Unwraps a kernel result where the shapes are fixed by the caller's
+    /// types. An error there is an internal bug, reported as loudly as the
+    /// out-of-bounds index it replaces.
+    #[inline(always)]
+    pub(crate) fn unwrap_kernel<T>(res: Result<T, MirError>) -> T {
+        match res {
+            Ok(v) => v,
+            Err(err) => panic!("kernel shape mismatch: {}", err),
+        }
+    }
 
-        idx += 1;
+    /// Computes `out = round(A * s)`.
+    pub fn matvec(a: &[i16], s: &[i16], out: &mut [u8]) {
+        unwrap_kernel(try_matvec(a, s, out))
     }
 
-    ct.cnt = widx as u16;
+    /// Fallible `matvec`.
+    ///
+    /// # Errors
+    /// `MirError::BadLength` if the shapes disagree (`debug-bounds` feature;
+    /// without it a mismatch panics on the index).
+    pub fn try_matvec(a: &[i16], s: &[i16], out: &mut [u8]) -> Result<(), MirError> {
+        let k = s.len() / N;
+        let mut i = 0;
+        while i < k {
+            let mut j = 0;
+            while j < N {
+                put(out, i * N + j, round(column_acc(a, |l| i * k + l, s, j)?))?;
+                j += 1;
+            }
+            i += 1;
+        }
+        Ok(())
+    }
 
-    // KDF: SHA3-256
-    let mut shared = MirSharedKey { key: [0u8; SHARED_LEN] };
-    mir_sha3_256(&mut shared.key, &buf[..widx], DOM_HASH);
+    /// Computes `out = round(A^T * r)`.
+    pub fn matvec_transpose(a: &[i16], r: &[i16], out: &mut [u8]) {
+        unwrap_kernel(try_matvec_transpose(a, r, out))
+    }
 
-    // Cleanup sensitive data
-    r.zeroize();
-    v.zeroize();
-    buf.zeroize();
+    /// Fallible `matvec_transpose`; errors as `try_matvec`.
+    pub fn try_matvec_transpose(a: &[i16], r: &[i16], out: &mut [u8]) -> Result<(), MirError> {
+        let k = r.len() / N;
+        let mut i = 0;
+        while i < k {
+            let mut j = 0;
+            while j < N {
+                put(out, i * N + j, round(column_acc(a, |l| l * k + i, r, j)?))?;
+                j += 1;
+            }
+            i += 1;
+        }
+        Ok(())
+    }
 
-    (ct, shared)
-}
+    /// `matvec` that streams `A` from `seed` instead of reading an expanded matrix.
+    ///
+    /// Holds one block of `A` at a time, for allocator-free targets that
+    /// cannot afford the full `K * K * N` matrix on the stack.
+    pub fn matvec_streamed(seed: &[u8; SEED_LEN], s: &[i16], out: &mut [u8]) {
+        unwrap_kernel(streamed_product(seed, s, false, out))
+    }
 
-/* === [9. Decapsulation] === */
+    /// `matvec_transpose` that streams `A` from `seed`; see `matvec_streamed`.
+    pub fn matvec_transpose_streamed(seed: &[u8; SEED_LEN], r: &[i16], out: &mut [u8]) {
+        unwrap_kernel(streamed_product(seed, r, true, out))
+    }
 
-/// Decapsulates a shared secret using the secret vault.
-///
-/// # Arguments
-/// * `ct` - The received Ciphertext.
-/// * `vault` - The recipient's Secret Vault.
-pub fn decaps(ct: &MirCiphertext, vault: &MirSecretVault) -> MirSharedKey {
-    // Access secret key securely
-    let v_prime = vault.access(|s| {
-        let mut vp = [0u8; N];
+    /// Block `(i, l)` of `A` adds to output block `i` (or `l` when
+    /// `transpose`), multiplied by input block `l` (or `i`). The accumulation
+    /// wraps exactly like `column_acc`, so the sums are identical.
+    fn streamed_product(seed: &[u8; SEED_LEN], x: &[i16], transpose: bool, out: &mut [u8]) -> Result<(), MirError> {
+        let mut stream = MatrixStream::new(seed);
+        let mut block = [0i16; N];
+        let mut acc = ScratchGuard::new([0i32; K * N]);
 
-        let mut j = 0;
-        while j < N {
-            let mut acc: i32 = 0;
+        let mut i = 0;
+        while i < K {
             let mut l = 0;
             while l < K {
-                let idx_u = (l * N) + j;
-                let idx_s = (l * N) + j;
-                let term = (ct.u[idx_u] as i32).wrapping_mul(s[idx_s] as i32);
-                acc = acc.wrapping_add(term);
+                stream.fill(&mut block);
+                let (dst, src) = if transpose { (l, i) } else { (i, l) };
+
+                let mut j = 0;
+                while j < N {
+                    let term = (block[j] as i32).wrapping_mul(at(x, src * N + j)? as i32);
+                    acc[dst * N + j] = acc[dst * N + j].wrapping_add(term);
+                    j += 1;
+                }
                 l += 1;
             }
-            vp[j] = (acc & 0xFF) as u8;
-            j += 1;
+            i += 1;
         }
 
-        vp
-    });
-
-    // Mask filtering (Reconciliation)
-    let mut buf = [0u8; N];
-    let mut widx: usize = 0;
-
-    let mut idx = 0;
-    while idx < N {
-        let val = v_prime[idx];
-        let sel = ct::bit_get(&ct.mask, idx);
-        let bit = (val >> 6) & 1;
+        let mut idx = 0;
+        while idx < K * N {
+            put(out, idx, round(acc[idx]))?;
+            idx += 1;
+        }
+        Ok(())
+    }
 
-        buf[widx] = ct::sel_u8(bit, buf[widx], sel);
-        widx = widx.wrapping_add(sel as usize);
+    /// Computes the block-wise inner product `out[j] = sum_l x[l][j] * y[l][j] mod p`
+    /// of a rounded vector `x` (such as `b` or `u`) with a ternary vector `y`.
+    pub fn inner_product(x: &[u8], y: &[i16], out: &mut [u8]) {
+        unwrap_kernel(try_inner_product(x, y, out))
+    }
 
-        idx += 1;
+    /// Fallible `inner_product`; errors as `try_matvec`.
+    pub fn try_inner_product(x: &[u8], y: &[i16], out: &mut [u8]) -> Result<(), MirError> {
+        let mut j = 0;
+        while j < N {
+            put(out, j, reduce_p(column_acc(x, |l| l, y, j)?))?;
+            j += 1;
+        }
+        Ok(())
     }
+}
 
-    // KDF: SHA3-256 (Same Domain)
-    let mut shared = MirSharedKey { key: [0u8; SHARED_LEN] };
-    mir_sha3_256(&mut shared.key, &buf[..widx], DOM_HASH);
+/// Thread-safe LRU cache of expanded matrices, keyed by `pk.seed` (`std` only).
+///
+/// `A` depends on nothing but the seed, so every key sharing a seed shares
+/// an entry. Matrices are public; nothing here needs scrubbing.
+#[cfg(feature = "std")]
+pub struct MatrixCache {
+    capacity: usize,
+    inner: std::sync::Mutex<MatrixCacheInner>,
+}
 
-    shared
+#[cfg(feature = "std")]
+struct MatrixCacheInner {
+    tick: u64,
+    entries: std::collections::HashMap<[u8; SEED_LEN], (std::sync::Arc<Vec<i16>>, u64)>,
 }
 
-/* === [10. Self Test & Verification] === */
+#[cfg(feature = "std")]
+impl MatrixCache {
+    /// Creates a cache holding at most `capacity` matrices (at least one).
+    pub fn new(capacity: usize) -> Self {
+        MatrixCache {
+            capacity: if capacity == 0 { 1 } else { capacity },
+            inner: std::sync::Mutex::new(MatrixCacheInner {
+                tick: 0,
+                entries: std::collections::HashMap::new(),
+            }),
+        }
+    }
 
-/// Performs a self-test of the Key Encapsulation Mechanism.
-/// Returns `true` if Alice and Bob derive the same shared secret.
-pub fn self_test() -> bool {
-    let (pk, vault) = keygen();
-    let (ct, key_bob) = encaps(&pk);
-    let key_alice = decaps(&ct, &vault);
+    /// Returns the matrix for `pk.seed`, expanding (and possibly evicting the
+    /// least recently used entry) on a miss.
+    ///
+    /// The expansion runs outside the lock, so concurrent misses on different
+    /// keys do not serialize behind SHAKE.
+    pub fn get_or_expand(&self, pk: &MirPubkey) -> std::sync::Arc<Vec<i16>> {
+        {
+            let mut inner = self.lock();
+            inner.tick += 1;
+            let tick = inner.tick;
+            if let Some(entry) = inner.entries.get_mut(&pk.seed) {
+                entry.1 = tick;
+                return entry.0.clone();
+            }
+        }
 
-    ct::eq_slice(&key_alice.key, &key_bob.key)
-}
+        let matrix = std::sync::Arc::new(poly::gen_matrix(&pk.seed));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let mut inner = self.lock();
+        inner.tick += 1;
+        let tick = inner.tick;
+        if let Some(entry) = inner.entries.get_mut(&pk.seed) {
+            // Another thread expanded it first; keep a single shared copy.
+            entry.1 = tick;
+            return entry.0.clone();
+        }
+        if inner.entries.len() >= self.capacity {
+            let oldest = inner.entries.iter().min_by_key(|(_, entry)| entry.1).map(|(seed, _)| *seed);
+            if let Some(seed) = oldest {
+                inner.entries.remove(&seed);
+            }
+        }
+        inner.entries.insert(pk.seed, (matrix.clone(), tick));
+        matrix
+    }
 
-    #[test]
-    fn test_correctness() {
-        assert!(self_test(), "Key exchange failed");
+    /// Number of cached matrices.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
     }
 
-    #[test]
-    fn test_stress_repeat() {
-        for _ in 0..100 {
-            assert!(self_test());
-        }
+    /// Returns `true` if nothing is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
-    #[test]
-    fn test_constant_time_ops() {
+    fn lock(&self) -> std::sync::MutexGuard<'_, MatrixCacheInner> {
+        // The cached data is immutable once inserted, so a poisoned lock is safe to reuse.
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A public key bundled with its expanded matrix `A`, for senders that
+/// encapsulate to the same recipient many times (`encaps_expanded`).
+///
+/// `A` is never written after expansion, so the type is `Send + Sync`: one
+/// `Arc<MirPubkeyExpanded>` can serve every worker of a thread pool, and
+/// cloning one only bumps the matrix's reference count. Without `alloc` the
+/// matrix (`K * K * N` coefficients, 12.8 KB) is held inline instead, and a
+/// clone copies it.
+#[derive(Clone)]
+pub struct MirPubkeyExpanded {
+    pk: MirPubkey,
+    matrix: SharedMatrix,
+}
+
+#[cfg(feature = "alloc")]
+type SharedMatrix = alloc::sync::Arc<Vec<i16>>;
+#[cfg(not(feature = "alloc"))]
+type SharedMatrix = poly::Matrix;
+
+// Fails to compile if a refactor ever adds a non-`Send`/non-`Sync` field.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<MirPubkeyExpanded>();
+};
+
+impl MirPubkeyExpanded {
+    /// Expands `A` for `pk.seed`.
+    pub fn new(pk: MirPubkey) -> Self {
+        let matrix = poly::gen_matrix(&pk.seed);
+        #[cfg(feature = "alloc")]
+        let matrix = alloc::sync::Arc::new(matrix);
+        MirPubkeyExpanded { pk, matrix }
+    }
+
+    /// Takes `A` from `cache`, sharing the entry instead of expanding again.
+    #[cfg(feature = "std")]
+    pub fn from_cache(pk: MirPubkey, cache: &MatrixCache) -> Self {
+        let matrix = cache.get_or_expand(&pk);
+        MirPubkeyExpanded { pk, matrix }
+    }
+
+    /// The public key `A` was expanded for.
+    pub fn public_key(&self) -> &MirPubkey {
+        &self.pk
+    }
+
+    /// The expanded matrix, `K * K * N` coefficients.
+    pub fn matrix(&self) -> &[i16] {
+        &self.matrix[..]
+    }
+}
+
+/* === [8. Secret Generation] === */
+
+/// Expands a ternary vector from `seed`.
+///
+/// Requires `seed.len() >= MIN_SEED_LEN`; a shorter seed silently caps the
+/// secret's entropy. Public seeded constructors enforce this with
+/// `MirError::BadLength`; internal callers always pass 32 bytes.
+fn gen_secret_from_seed(seed: &[u8], out_s: &mut [i16]) {
+    debug_assert!(seed.len() >= MIN_SEED_LEN);
+
+    let mut reader = mir_shake_reader(seed, domains::SECRET);
+    let mut buf = ScratchGuard::new([0u8; N]);
+
+    let len = out_s.len();
+    let mut i = 0;
+    while i < len {
+        let take = if len - i < N { len - i } else { N };
+        reader.read(&mut buf[..take]);
+
+        let mut j = 0;
+        while j < take {
+            out_s[i + j] = ct::ternary(buf[j]);
+            j += 1;
+        }
+        i += take;
+    }
+}
+
+/* === [9. Key Generation] === */
+
+/// Generates a new Mirletis key pair.
+///
+/// # Returns
+/// A tuple containing (`MirPubkey`, `MirSecretVault`).
+pub fn keygen() -> (MirPubkey, MirSecretVault) {
+    keygen_with_rng(&mut DefaultRng::default())
+}
+
+/// Fallible `keygen`: an unavailable OS RNG is an error, not a panic.
+///
+/// # Errors
+/// `MirError::Rng` if `DefaultRng` cannot produce the seed, and
+/// `MirError::InsufficientEntropy` if it only ever yields weak secrets.
+pub fn try_keygen() -> Result<(MirPubkey, MirSecretVault), MirError> {
+    keygen_fresh(&mut DefaultRng::default())
+}
+
+/// Generates a new Mirletis key pair from a caller-supplied CSPRNG.
+///
+/// Use this to plug in a hardware RNG or to reseed a CSPRNG deterministically.
+///
+/// # Arguments
+/// * `rng` - A cryptographically secure random number generator.
+///
+/// # Returns
+/// A tuple containing (`MirPubkey`, `MirSecretVault`).
+///
+/// # Panics
+/// If `rng` fails, or if every one of `KEYGEN_ATTEMPTS` draws yields a weak
+/// secret (see `MIN_SECRET_WEIGHT`), which points at a broken `rng`.
+pub fn keygen_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> (MirPubkey, MirSecretVault) {
+    match keygen_fresh(rng) {
+        Ok(pair) => pair,
+        Err(err) => panic!("Mirletis keygen failed: {:?}", err),
+    }
+}
+
+/// Counts the nonzero coefficients of a ternary secret, without branching on them.
+fn secret_weight(s: &[i16]) -> usize {
+    let mut weight = 0usize;
+    let mut i = 0;
+    while i < s.len() {
+        // -1 and +1 both have the low bit set; 0 does not
+        weight += (s[i] & 1) as usize;
+        i += 1;
+    }
+    weight
+}
+
+/// A secret vault of any rank, as far as the keygen drivers are concerned.
+trait KeygenVault {
+    /// Whether the secret falls below `MIN_SECRET_WEIGHT` at its rank.
+    fn is_weak(&self) -> bool;
+}
+
+impl KeygenVault for MirSecretVault {
+    fn is_weak(&self) -> bool {
+        self.access(secret_weight) < MIN_SECRET_WEIGHT
+    }
+}
+
+/// Key generation from fresh entropy that re-draws weak secrets.
+fn keygen_fresh<R: RngCore>(rng: &mut R) -> Result<(MirPubkey, MirSecretVault), MirError> {
+    keygen_fresh_with(rng, keygen_from_master)
+}
+
+/// `keygen_fresh` over a rank-specific `derive` from the 64-byte master
+/// seed, shared with the const-generic parameter sets.
+///
+/// The weight check branches only on whether the whole secret is rejected,
+/// which a discarded candidate does not need to hide.
+fn keygen_fresh_with<P, V: KeygenVault, R: RngCore>(rng: &mut R, derive: fn(&[u8; 64]) -> (P, V)) -> Result<(P, V), MirError> {
+    // 1. Generate two independent seeds from the RNG (scrubbed on return)
+    let mut master_seed = ScratchGuard::new([0u8; 64]);
+
+    let mut attempt = 0;
+    while attempt < KEYGEN_ATTEMPTS {
+        try_fill(rng, &mut *master_seed)?;
+        let (pk, vault) = derive(&master_seed);
+        if !vault.is_weak() {
+            return Ok((pk, vault));
+        }
+        attempt += 1;
+    }
+    Err(MirError::InsufficientEntropy)
+}
+
+/// `keygen_with_rng` followed by a pairwise consistency test.
+///
+/// The new key immediately runs one encaps/decaps round trip (ephemeral
+/// coins drawn from `rng`) and is only returned if both sides agree, as
+/// FIPS-style implementations do to catch faults during generation.
+///
+/// # Errors
+/// `MirError::InconsistentKeypair` if the round trip disagrees, and
+/// otherwise as `try_keygen`.
+pub fn keygen_checked<R: RngCore + CryptoRng>(rng: &mut R) -> Result<(MirPubkey, MirSecretVault), MirError> {
+    let (pk, vault) = keygen_fresh(rng)?;
+    if !pairwise_consistent(&pk, &vault, rng) {
+        return Err(MirError::InconsistentKeypair);
+    }
+    Ok((pk, vault))
+}
+
+/// One `self_test` round trip, scoped to a given key pair.
+fn pairwise_consistent<R: RngCore + CryptoRng>(pk: &MirPubkey, vault: &MirSecretVault, rng: &mut R) -> bool {
+    let (ct, key_bob) = encaps_with_rng(pk, rng);
+    let key_alice = decaps(&ct, vault);
+
+    ct::eq_slice(&key_alice.key, &key_bob.key)
+}
+
+/// Deterministically derives a key pair from `seed`.
+///
+/// The same seed always yields the same key pair, so `seed` is as sensitive
+/// as the secret key itself. It is expanded with SHAKE-256 under a dedicated
+/// domain into the two 32-byte seeds that `keygen` would otherwise draw.
+///
+/// A seed is not fresh entropy, so a weak secret cannot be re-drawn here;
+/// it is reported instead (with the same negligible odds as in `keygen`).
+///
+/// # Errors
+/// `MirError::BadLength` if `seed` is shorter than `MIN_SEED_LEN` bytes, and
+/// `MirError::InsufficientEntropy` if it derives a weak secret.
+pub fn keygen_from_seed(seed: &[u8]) -> Result<(MirPubkey, MirSecretVault), MirError> {
+    keygen_seeded_with(seed, keygen_from_master)
+}
+
+/// `keygen_from_seed` over a rank-specific `derive`; see `keygen_fresh_with`.
+fn keygen_seeded_with<P, V: KeygenVault>(seed: &[u8], derive: fn(&[u8; 64]) -> (P, V)) -> Result<(P, V), MirError> {
+    if seed.len() < MIN_SEED_LEN {
+        return Err(MirError::BadLength);
+    }
+
+    let mut master_seed = ScratchGuard::new([0u8; 64]);
+    mir_shake_reader(seed, domains::KEYGEN).read(&mut *master_seed);
+
+    let (pk, vault) = derive(&master_seed);
+    if vault.is_weak() {
+        return Err(MirError::InsufficientEntropy);
+    }
+    Ok((pk, vault))
+}
+
+/// Derives the `index`-th key pair of a deterministic hierarchy rooted at `master`.
+///
+/// The per-index seed is `SHAKE256(KEYGEN_INDEX || master || index (4-byte LE))`,
+/// which then goes through `keygen_from_seed`. The same `(master, index)`
+/// always reproduces the same key pair, while distinct indices give
+/// independent ones, so backing up `master` backs up every key derived from
+/// it. `master` is as sensitive as all of those secret keys together.
+///
+/// # Errors
+/// `MirError::InsufficientEntropy` if the index derives a weak secret, as
+/// `keygen_from_seed`; moving on to the next index is then safe.
+pub fn keygen_indexed(master: &[u8; 32], index: u32) -> Result<(MirPubkey, MirSecretVault), MirError> {
+    let mut hasher = Shake256::default();
+    hasher.update(&[domains::KEYGEN_INDEX]);
+    hasher.update(master);
+    hasher.update(&index.to_le_bytes());
+
+    let mut seed = ScratchGuard::new([0u8; 32]);
+    hasher.finalize_xof().read(&mut *seed);
+
+    keygen_from_seed(&*seed)
+}
+
+fn keygen_from_master(master_seed: &[u8; 64]) -> (MirPubkey, MirSecretVault) {
+    let mut pk = MirPubkey::default();
+
+    // 2. Public Key Seed (Front 32 bytes)
+    pk.seed.copy_from_slice(&master_seed[..32]);
+
+    // 3. Secret Key Seed (Back 32 bytes) - Independent derivation
+    let mut vault = MirSecretVault { secret_s: zeroed_secret(), secret_seed: None };
+    gen_secret_from_seed(&master_seed[32..], &mut vault.secret_s);
+
+    // 4. Generate Matrix A from seed
+    let matrix_a = poly::gen_matrix(&pk.seed);
+
+    // 5. Compute b = A * s (Component-wise / Parallel)
+    poly::matvec(&matrix_a, &vault.secret_s, &mut pk.b);
+
+    // 6. Keep the secret seed in the Vault too, for backup export
+    vault.secret_seed.insert([0u8; 32]).copy_from_slice(&master_seed[32..]);
+
+    (pk, vault)
+}
+
+/// Recomputes the public key for `seed` from the secret held in `vault`.
+///
+/// Comparing the result against a received `MirPubkey` verifies that the
+/// key pair belongs together.
+pub fn public_from_vault(seed: &[u8; SEED_LEN], vault: &MirSecretVault) -> MirPubkey {
+    let mut pk = MirPubkey {
+        seed: *seed,
+        b: [0u8; K * N],
+    };
+
+    let matrix_a = poly::gen_matrix(&pk.seed);
+    vault.access(|s| poly::matvec(&matrix_a, s, &mut pk.b));
+
+    pk
+}
+
+/// A public key together with the vault holding its secret.
+pub struct MirKeypair {
+    pub public: MirPubkey,
+    pub vault: MirSecretVault,
+}
+
+impl MirKeypair {
+    /// Generates a fresh key pair (`keygen`).
+    pub fn generate() -> Self {
+        let (public, vault) = keygen();
+        MirKeypair { public, vault }
+    }
+
+    /// Draws a new matrix seed from `rng` and recomputes `b = A_new * s`,
+    /// keeping the secret `s` (and the vault) unchanged.
+    ///
+    /// # Security
+    /// This is partial re-keying, not forward secrecy. Every published `b` is
+    /// another `K * N` rounding samples of the *same* `s`, so each rotation
+    /// hands an attacker more equations against one secret; the parameter
+    /// analysis assumes a single `b`. Compromise of the vault still exposes
+    /// every epoch, and since decapsulation only ever uses `s`, ciphertexts
+    /// made for an old public key keep decapsulating; rotation stops nothing
+    /// by itself. Redistribute `public` and withdraw the old key out of band.
+    pub fn rotate_public_seed<R: RngCore + CryptoRng>(&mut self, rng: &mut R) {
+        let mut seed = [0u8; SEED_LEN];
+        rng.fill_bytes(&mut seed);
+        self.public = public_from_vault(&seed, &self.vault);
+    }
+}
+
+impl From<(MirPubkey, MirSecretVault)> for MirKeypair {
+    fn from((public, vault): (MirPubkey, MirSecretVault)) -> Self {
+        MirKeypair { public, vault }
+    }
+}
+
+/* === [10. Encapsulation] === */
+
+/// Encapsulates a shared secret for the given public key.
+///
+/// # Arguments
+/// * `pk` - The recipient's Public Key.
+///
+/// # Returns
+/// A tuple containing (`MirCiphertext`, `MirSharedKey`).
+pub fn encaps(pk: &MirPubkey) -> (MirCiphertext, MirSharedKey) {
+    encaps_with_rng(pk, &mut DefaultRng::default())
+}
+
+/// Fallible `encaps`: an unavailable OS RNG is an error, not a panic.
+///
+/// # Errors
+/// `MirError::Rng` if `DefaultRng` cannot produce the ephemeral seed.
+pub fn try_encaps(pk: &MirPubkey) -> Result<(MirCiphertext, MirSharedKey), MirError> {
+    let mut eph_seed = ScratchGuard::new([0u8; 32]);
+    try_fill(&mut DefaultRng::default(), &mut *eph_seed)?;
+
+    Ok(encaps_deterministic(pk, &eph_seed))
+}
+
+/// Encapsulates a shared secret using a caller-supplied CSPRNG.
+///
+/// # Arguments
+/// * `pk` - The recipient's Public Key.
+/// * `rng` - A cryptographically secure random number generator.
+///
+/// # Returns
+/// A tuple containing (`MirCiphertext`, `MirSharedKey`).
+pub fn encaps_with_rng<R: RngCore + CryptoRng>(pk: &MirPubkey, rng: &mut R) -> (MirCiphertext, MirSharedKey) {
+    // Generate ephemeral entropy
+    let mut eph_seed = ScratchGuard::new([0u8; 32]);
+    rng.fill_bytes(&mut *eph_seed);
+
+    encaps_deterministic(pk, &eph_seed)
+}
+
+/// Encapsulates using `coins` as the ephemeral seed instead of fresh randomness.
+///
+/// Identical `(pk, coins)` always produce a byte-identical ciphertext and key,
+/// which is what re-encryption checks and known-answer tests need.
+/// `coins` must be secret and never reused across encapsulations.
+///
+/// # Arguments
+/// * `pk` - The recipient's Public Key.
+/// * `coins` - 32 bytes of secret ephemeral entropy.
+///
+/// # Returns
+/// A tuple containing (`MirCiphertext`, `MirSharedKey`).
+pub fn encaps_deterministic(pk: &MirPubkey, coins: &[u8; 32]) -> (MirCiphertext, MirSharedKey) {
+    let mut r = ScratchGuard::new([0i16; K * N]);
+    let mut ct = MirCiphertext::default();
+    let mut shared = MirSharedKey { key: [0u8; SHARED_LEN] };
+    let res = encaps_core(pk, &poly::gen_matrix(&pk.seed), coins, &mut *r, &mut ct, &[], &mut shared.key);
+    poly::unwrap_kernel(res);
+
+    (ct, shared)
+}
+
+/// `encaps_deterministic` that refuses coins yielding fewer than `min_cnt` safe bits.
+///
+/// The whole exchange, including which positions land in the safe zone, is a
+/// pure function of `(pk, coins)`, so a given pair either always passes or
+/// always fails. Tests can pin such a pair without ever flaking on a weak key.
+///
+/// # Errors
+/// `MirError::InsufficientEntropy` if the ciphertext's `cnt` is below `min_cnt`.
+pub fn encaps_deterministic_checked(
+    pk: &MirPubkey,
+    coins: &[u8; 32],
+    min_cnt: u16,
+) -> Result<(MirCiphertext, MirSharedKey), MirError> {
+    let (ct, shared) = encaps_deterministic(pk, coins);
+    if ct.cnt < min_cnt {
+        return Err(MirError::InsufficientEntropy);
+    }
+    Ok((ct, shared))
+}
+
+/// Deterministic (convergent) encapsulation: the coins are derived from the message.
+///
+/// The coins are `SHAKE256(ENCAPS_DET || seed || b || message)`, so the same
+/// message to the same key always yields the same ciphertext and key.
+///
+/// **Not IND-CPA.** Anyone holding `pk` can test a guessed `message` against
+/// an observed ciphertext, and equal messages are visibly equal. Use this only
+/// for deduplication-style designs where that is the point, and only with
+/// high-entropy messages.
+///
+/// # Returns
+/// A tuple containing (`MirCiphertext`, `MirSharedKey`).
+pub fn encaps_det(pk: &MirPubkey, message: &[u8]) -> (MirCiphertext, MirSharedKey) {
+    let mut coins = ScratchGuard::new([0u8; 32]);
+    derive_det_coins(pk, message, &mut coins);
+
+    encaps_deterministic(pk, &coins)
+}
+
+fn derive_det_coins(pk: &MirPubkey, message: &[u8], coins: &mut [u8; 32]) {
+    let mut hasher = Shake256::default();
+    hasher.update(&[domains::ENCAPS_DET]);
+    hasher.update(&pk.seed);
+    hasher.update(&pk.b);
+    hasher.update(message);
+    hasher.finalize_xof().read(coins);
+}
+
+/// Encapsulates into caller-provided buffers, without allocating.
+///
+/// The ephemeral secret `r` is derived into `scratch`, which is zeroized
+/// before returning. Matrix `A` is streamed from `pk.seed` one block at a
+/// time (`poly::matvec_transpose_streamed`) instead of being expanded, so the
+/// call touches neither the heap nor a `K * K * N` stack matrix.
+///
+/// # Arguments
+/// * `pk` - The recipient's Public Key.
+/// * `rng` - A cryptographically secure random number generator.
+/// * `scratch` - Reusable buffer of at least `K * N` coefficients.
+/// * `ct_out` - Receives the ciphertext; any previous contents are overwritten.
+///
+/// # Errors
+/// `MirError::BadLength` if `scratch` is shorter than `K * N`, and
+/// `MirError::Rng` if `rng` fails. On any error `ct_out` is reset to
+/// `MirCiphertext::default()` (all zero, `cnt = 0`), so a half-written
+/// ciphertext is never left behind.
+pub fn encaps_into<R: RngCore + CryptoRng>(
+    pk: &MirPubkey,
+    rng: &mut R,
+    scratch: &mut [i16],
+    ct_out: &mut MirCiphertext,
+) -> Result<MirSharedKey, MirError> {
+    let res = encaps_into_inner(pk, rng, scratch, ct_out);
+    if res.is_err() {
+        *ct_out = MirCiphertext::default();
+    }
+    res
+}
+
+fn encaps_into_inner<R: RngCore + CryptoRng>(
+    pk: &MirPubkey,
+    rng: &mut R,
+    scratch: &mut [i16],
+    ct_out: &mut MirCiphertext,
+) -> Result<MirSharedKey, MirError> {
+    if scratch.len() < K * N {
+        return Err(MirError::BadLength);
+    }
+
+    let mut eph_seed = ScratchGuard::new([0u8; 32]);
+    try_fill(rng, &mut *eph_seed)?;
+
+    let r = &mut scratch[..K * N];
+    gen_secret_from_seed(&*eph_seed, r);
+
+    // Compute u = A^T * r, streaming A
+    poly::matvec_transpose_streamed(&pk.seed, r, &mut ct_out.u);
+
+    let mut shared = MirSharedKey { key: [0u8; SHARED_LEN] };
+    let res = encaps_finish(&pk.b, r, &mut ct_out.mask, &[], &mut shared.key).map(|cnt| ct_out.cnt = cnt);
+
+    // The scratch buffer is caller-owned, so it is scrubbed explicitly.
+    r.zeroize();
+
+    res.map(|()| shared)
+}
+
+/// Encapsulates against an already-expanded matrix `A`, e.g. from `MatrixCache`.
+///
+/// `matrix_a` must be the expansion of `pk.seed` (`poly::gen_matrix`); a
+/// matrix for another seed yields a ciphertext the recipient cannot open.
+///
+/// # Errors
+/// `MirError::BadLength` if `matrix_a` is not exactly `K * K * N` long, and
+/// `MirError::Rng` if `rng` fails.
+pub fn encaps_with_matrix<R: RngCore + CryptoRng>(
+    pk: &MirPubkey,
+    matrix_a: &[i16],
+    rng: &mut R,
+) -> Result<(MirCiphertext, MirSharedKey), MirError> {
+    if matrix_a.len() != K * K * N {
+        return Err(MirError::BadLength);
+    }
+
+    let mut eph_seed = ScratchGuard::new([0u8; 32]);
+    try_fill(rng, &mut *eph_seed)?;
+
+    let mut r = ScratchGuard::new([0i16; K * N]);
+    let mut ct = MirCiphertext::default();
+    let mut shared = MirSharedKey { key: [0u8; SHARED_LEN] };
+    encaps_core(pk, matrix_a, &eph_seed, &mut *r, &mut ct, &[], &mut shared.key)?;
+
+    Ok((ct, shared))
+}
+
+/// Encapsulates against a pre-expanded public key, skipping the expansion of `A`.
+///
+/// Takes the key by reference, so an `&Arc<MirPubkeyExpanded>` shared by a
+/// thread pool is passed as is; the output matches `encaps` for the same key.
+pub fn encaps_expanded(pk: &MirPubkeyExpanded) -> (MirCiphertext, MirSharedKey) {
+    encaps_expanded_with_rng(pk, &mut DefaultRng::default())
+}
+
+/// `encaps_expanded` with a caller-supplied CSPRNG.
+pub fn encaps_expanded_with_rng<R: RngCore + CryptoRng>(
+    pk: &MirPubkeyExpanded,
+    rng: &mut R,
+) -> (MirCiphertext, MirSharedKey) {
+    let mut eph_seed = ScratchGuard::new([0u8; 32]);
+    rng.fill_bytes(&mut *eph_seed);
+
+    let mut r = ScratchGuard::new([0i16; K * N]);
+    let mut ct = MirCiphertext::default();
+    let mut shared = MirSharedKey { key: [0u8; SHARED_LEN] };
+    let res = encaps_core(&pk.pk, pk.matrix(), &eph_seed, &mut *r, &mut ct, &[], &mut shared.key);
+    poly::unwrap_kernel(res);
+
+    (ct, shared)
+}
+
+/// Shared body of all encapsulation entry points.
+/// Derives `r` from `eph_seed` into the caller's buffer, fills `ct` and
+/// squeezes `key_out.len()` bytes of shared secret (bound to `context`) into `key_out`;
+/// scrubbing `r` is the caller's responsibility. `matrix_a` must be the
+/// expansion of `pk.seed`. Errors only come from the kernels (`poly::try_matvec`).
+fn encaps_core(
+    pk: &MirPubkey,
+    matrix_a: &[i16],
+    eph_seed: &[u8; 32],
+    r: &mut [i16],
+    ct: &mut MirCiphertext,
+    context: &[u8],
+    key_out: &mut [u8],
+) -> Result<(), MirError> {
+    gen_secret_from_seed(eph_seed, r);
+
+    // Compute u = A^T * r
+    poly::try_matvec_transpose(matrix_a, r, &mut ct.u)?;
+
+    ct.cnt = encaps_finish(&pk.b, r, &mut ct.mask, context, key_out)?;
+    Ok(())
+}
+
+/// Second half of `encaps_core`, once `u = A^T * r` is computed: computes
+/// `v = b * r`, fills the safe-zone `mask` and squeezes the shared secret.
+/// Returns the safe-bit count. Works on any block count (see `MirPubkeyG`).
+fn encaps_finish(
+    b: &[u8],
+    r: &[i16],
+    mask: &mut [u8; N / 8],
+    context: &[u8],
+    key_out: &mut [u8],
+) -> Result<u16, MirError> {
+    // Compute v = b * r
+    let mut v = ScratchGuard::new([0u8; N]);
+    poly::try_inner_product(b, r, &mut *v)?;
+
+    let cnt = select_safe_zone(&v, mask);
+
+    // KDF: SHAKE-256 over the safe-zone bits (length-prefixed)
+    derive_shared_bytes(&v, mask, cnt, context, key_out);
+    Ok(cnt)
+}
+
+/// Safe-zone selection: sets bit `idx` of `mask` for every coefficient of `v`
+/// in the safe zone and returns how many were set.
+fn select_safe_zone(v: &[u8; N], mask: &mut [u8; N / 8]) -> u16 {
+    *mask = [0u8; N / 8];
+    let mut widx: usize = 0;
+
+    let mut idx = 0;
+    while idx < N {
+        let safe = ct::safe_zone(v[idx]);
+        ct::bit_set(mask, idx, safe);
+        widx = widx.wrapping_add(safe as usize);
+
+        idx += 1;
+    }
+
+    widx as u16
+}
+
+/// Encapsulates a shared secret of arbitrary length (e.g. 48 bytes for an
+/// AES-256 key plus a nonce seed) into `out`.
+///
+/// `encaps` is the 32-byte case of the same SHAKE-256 squeeze. The output
+/// length is absorbed before squeezing, so a 48-byte secret does not start
+/// with the 32-byte one for the same exchange; the recipient must ask
+/// `decaps_bytes` for the same length.
+pub fn encaps_bytes(pk: &MirPubkey, out: &mut [u8]) -> MirCiphertext {
+    encaps_bytes_with_rng(pk, &mut DefaultRng::default(), out)
+}
+
+/// `encaps_bytes` with a caller-supplied CSPRNG.
+pub fn encaps_bytes_with_rng<R: RngCore + CryptoRng>(pk: &MirPubkey, rng: &mut R, out: &mut [u8]) -> MirCiphertext {
+    let mut eph_seed = ScratchGuard::new([0u8; 32]);
+    rng.fill_bytes(&mut *eph_seed);
+
+    let mut r = ScratchGuard::new([0i16; K * N]);
+    let mut ct = MirCiphertext::default();
+    poly::unwrap_kernel(encaps_core(pk, &poly::gen_matrix(&pk.seed), &eph_seed, &mut *r, &mut ct, &[], out));
+
+    ct
+}
+
+/// Encapsulates a shared secret and reports how many bits fed the KDF.
+///
+/// Callers can log `EncapsInfo::safe_bits` or reject exchanges below a minimum.
+///
+/// # Returns
+/// A tuple containing (`MirCiphertext`, `MirSharedKey`, `EncapsInfo`).
+pub fn encaps_with_info(pk: &MirPubkey) -> (MirCiphertext, MirSharedKey, EncapsInfo) {
+    let (ct, shared) = encaps(pk);
+    let info = EncapsInfo { safe_bits: ct.cnt };
+    (ct, shared, info)
+}
+
+/// Encapsulates and signs the ciphertext with a caller-supplied signer.
+///
+/// `sign` receives exactly the wire bytes (`MirCiphertext::to_bytes`), so any
+/// scheme (Ed25519, ML-DSA, ...) can be layered on without this crate
+/// depending on it.
+///
+/// The signature is whatever `sign` returns: a `Vec<u8>`, or a fixed-size
+/// array on targets without an allocator.
+///
+/// # Returns
+/// A tuple containing (`MirCiphertext`, signature, `MirSharedKey`).
+pub fn encaps_signed<R, F, S>(pk: &MirPubkey, rng: &mut R, sign: F) -> (MirCiphertext, S, MirSharedKey)
+where
+    R: RngCore + CryptoRng,
+    F: FnOnce(&[u8]) -> S,
+{
+    let (ct, shared) = encaps_with_rng(pk, rng);
+    let sig = sign(&ct.to_bytes());
+    (ct, sig, shared)
+}
+
+/// Integrity tag over a ciphertext: `SHA3-256(MAC || key || ct.to_bytes())`.
+///
+/// Keyed by the shared secret, so only the two parties can produce it. The
+/// tag leaks nothing useful about `key`, and the domain keeps it apart from
+/// any other hash of the key.
+#[cfg(feature = "mac")]
+fn mac_tag(key: &MirSharedKey, ct: &MirCiphertext) -> [u8; MAC_LEN] {
+    let mut tag = [0u8; MAC_LEN];
+    mir_sha3_256(&mut tag, &[&key.key, &ct.to_bytes()], domains::MAC);
+    tag
+}
+
+/// Encapsulates and tags the ciphertext with a MAC keyed by the shared
+/// secret (`mac` feature).
+///
+/// The tag catches corruption and naive tampering at `decaps_mac`. It is not
+/// a CCA transform: anyone who can encapsulate can also tag.
+///
+/// # Returns
+/// A tuple containing (`MirCiphertext`, tag, `MirSharedKey`).
+#[cfg(feature = "mac")]
+pub fn encaps_mac(pk: &MirPubkey) -> (MirCiphertext, [u8; MAC_LEN], MirSharedKey) {
+    encaps_mac_with_rng(pk, &mut DefaultRng::default())
+}
+
+/// `encaps_mac` with a caller-supplied RNG (`mac` feature).
+#[cfg(feature = "mac")]
+pub fn encaps_mac_with_rng<R: RngCore + CryptoRng>(
+    pk: &MirPubkey,
+    rng: &mut R,
+) -> (MirCiphertext, [u8; MAC_LEN], MirSharedKey) {
+    let (ct, shared) = encaps_with_rng(pk, rng);
+    let tag = mac_tag(&shared, &ct);
+    (ct, tag, shared)
+}
+
+/* === [11. Decapsulation] === */
+
+/// Decapsulates a shared secret using the secret vault.
+///
+/// # Arguments
+/// * `ct` - The received Ciphertext.
+/// * `vault` - The recipient's Secret Vault.
+pub fn decaps(ct: &MirCiphertext, vault: &MirSecretVault) -> MirSharedKey {
+    // Access secret key securely
+    vault.access(|s| decaps_with_secret(ct, s))
+}
+
+/// Decapsulates an `out.len()`-byte shared secret produced by `encaps_bytes`.
+pub fn decaps_bytes(ct: &MirCiphertext, vault: &MirSecretVault, out: &mut [u8]) {
+    poly::unwrap_kernel(vault.access(|s| decaps_bytes_with_secret(ct, s, &[], out)))
+}
+
+/// Decapsulates after checking the ciphertext's structure with `check`.
+///
+/// # Errors
+/// `MirError::OutOfRange` if `cnt > N` and `MirError::InconsistentCount` if
+/// `cnt` does not match the mask. With `debug-bounds`, `MirError::BadLength`
+/// if the vault's secret has the wrong shape.
+pub fn decaps_checked(ct: &MirCiphertext, vault: &MirSecretVault) -> Result<MirSharedKey, MirError> {
+    ct.check()?;
+    vault.access(|s| try_decaps_with_secret(ct, s))
+}
+
+/// Decapsulates straight from wire bytes: a public key from
+/// `MirPubkey::to_bytes`, a ciphertext from `MirCiphertext::to_bytes` and a
+/// secret-key seed from `export_secret_seed`.
+///
+/// Every input goes through a fallible constructor and the secret is checked
+/// against the public key before use, so arbitrary bytes only ever produce a
+/// `MirError`. This is the entry point of the `fuzz` targets.
+///
+/// # Errors
+/// Any error from `from_bytes` on `pk_bytes` or `ct_bytes`,
+/// `MirError::BadLength` if `sk_bytes` is not `SECRET_SEED_BYTES` long,
+/// `MirError::InconsistentKeypair` if the secret does not belong to the public
+/// key, and `MirError::InconsistentCount` as for `decaps_checked`.
+pub fn decaps_from_bytes(pk_bytes: &[u8], ct_bytes: &[u8], sk_bytes: &[u8]) -> Result<MirSharedKey, MirError> {
+    let pk = MirPubkey::from_bytes(pk_bytes)?;
+    let ct = MirCiphertext::from_bytes(ct_bytes)?;
+    let seed: [u8; SECRET_SEED_BYTES] = sk_bytes.try_into().map_err(|_| MirError::BadLength)?;
+
+    let vault = MirSecretVault::from_secret_seed(&MirSecretSeed::from_bytes(seed));
+    if !public_from_vault(&pk.seed, &vault).ct_eq(&pk) {
+        return Err(MirError::InconsistentKeypair);
+    }
+    decaps_checked(&ct, &vault)
+}
+
+/// Decapsulates a `MirCiphertext::to_bytes` encoding read incrementally
+/// from `r` (`std` feature).
+///
+/// The fields are read straight into place: header, `u`, `mask`, then
+/// `cnt`. The header is checked before anything else is read and `cnt`
+/// before the secret is touched, so a bad or truncated stream stops as
+/// early as possible. Nothing past the ciphertext is consumed.
+///
+/// # Errors
+/// `MirError::BadLength` if the stream ends early, `MirError::Io` for any
+/// other read error, header errors as for `MirCiphertext::from_bytes`, then
+/// as `decaps_checked`.
+#[cfg(feature = "std")]
+pub fn decaps_from_reader<R: std::io::Read>(r: &mut R, vault: &MirSecretVault) -> Result<MirSharedKey, MirError> {
+    fn fill<R: std::io::Read>(r: &mut R, buf: &mut [u8]) -> Result<(), MirError> {
+        r.read_exact(buf).map_err(|err| match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => MirError::BadLength,
+            _ => MirError::Io,
+        })
+    }
+
+    let mut header = [0u8; HEADER_BYTES];
+    fill(r, &mut header)?;
+    check_header(&header)?;
+
+    let mut ct = MirCiphertext::default();
+    fill(r, &mut ct.u)?;
+    fill(r, &mut ct.mask)?;
+    let mut cnt = [0u8; 2];
+    fill(r, &mut cnt)?;
+    ct.cnt = u16::from_le_bytes(cnt);
+
+    decaps_checked(&ct, vault)
+}
+
+/// Verifies a signature from `encaps_signed`, then decapsulates.
+///
+/// `verify(ct_bytes, sig)` sees the same wire bytes the signer saw. The
+/// secret is only touched once it returns `true`.
+///
+/// # Errors
+/// `MirError::BadSignature` if `verify` returns `false`.
+pub fn decaps_verified<F>(
+    ct: &MirCiphertext,
+    sig: &[u8],
+    vault: &MirSecretVault,
+    verify: F,
+) -> Result<MirSharedKey, MirError>
+where
+    F: FnOnce(&[u8], &[u8]) -> bool,
+{
+    if !verify(&ct.to_bytes(), sig) {
+        return Err(MirError::BadSignature);
+    }
+    Ok(decaps(ct, vault))
+}
+
+/// Decapsulates and checks the tag from `encaps_mac` (`mac` feature).
+///
+/// The tag is recomputed from the derived key and compared in constant time;
+/// on mismatch the key is wiped and never returned.
+///
+/// # Errors
+/// `MirError::InvalidCiphertext` if the tag does not match, which includes
+/// any change to the ciphertext or tag in transit.
+#[cfg(feature = "mac")]
+pub fn decaps_mac(ct: &MirCiphertext, tag: &[u8; MAC_LEN], vault: &MirSecretVault) -> Result<MirSharedKey, MirError> {
+    let shared = decaps(ct, vault);
+    if !ct::eq_slice(&mac_tag(&shared, ct), tag) {
+        return Err(MirError::InvalidCiphertext);
+    }
+    Ok(shared)
+}
+
+/// Decapsulates and flags validity as a `subtle::Choice` (`subtle` feature).
+///
+/// The key is always derived and the flag is the `is_valid` check computed
+/// without branching, so nothing about the outcome shows in the timing.
+/// `MirSharedKey::conditional_select` can then swap in a rejection key in
+/// constant time, which is the shape implicit-rejection CCA transforms need.
+#[cfg(feature = "subtle")]
+pub fn decaps_ct(ct: &MirCiphertext, vault: &MirSecretVault) -> (MirSharedKey, subtle::Choice) {
+    // `popcount <= N`, so equality also implies `cnt <= N`
+    let valid = ct::eq32(ct.cnt as i32, ct::popcount(&ct.mask) as i32);
+    (decaps(ct, vault), subtle::Choice::from(valid as u8))
+}
+
+/// Decapsulates a batch of ciphertexts addressed to the same vault.
+///
+/// The vault is accessed once for the whole batch. Each result matches a
+/// serial `decaps` call on the corresponding ciphertext.
+#[cfg(feature = "alloc")]
+pub fn decaps_batch(cts: &[MirCiphertext], vault: &MirSecretVault) -> Vec<MirSharedKey> {
+    vault.access(|s| cts.iter().map(|ct| decaps_with_secret(ct, s)).collect())
+}
+
+/// `decaps_batch` into caller-owned `out`, one key per ciphertext, for
+/// builds without an allocator.
+///
+/// # Errors
+/// `MirError::BadLength` if `out` and `cts` differ in length.
+pub fn decaps_batch_into(cts: &[MirCiphertext], vault: &MirSecretVault, out: &mut [MirSharedKey]) -> Result<(), MirError> {
+    if out.len() != cts.len() {
+        return Err(MirError::BadLength);
+    }
+    vault.access(|s| {
+        for (ct, key) in cts.iter().zip(out.iter_mut()) {
+            *key = decaps_with_secret(ct, s);
+        }
+    });
+    Ok(())
+}
+
+/// Parallel variant of `decaps_batch`, spreading ciphertexts across the rayon pool.
+///
+/// All worker threads share a read-only view of the same secret; nothing is cloned.
+#[cfg(feature = "rayon")]
+pub fn decaps_batch_par(cts: &[MirCiphertext], vault: &MirSecretVault) -> Vec<MirSharedKey> {
+    use rayon::prelude::*;
+    vault.access(|s| cts.par_iter().map(|ct| decaps_with_secret(ct, s)).collect())
+}
+
+fn decaps_with_secret(ct: &MirCiphertext, s: &[i16]) -> MirSharedKey {
+    poly::unwrap_kernel(try_decaps_with_secret(ct, s))
+}
+
+fn try_decaps_with_secret(ct: &MirCiphertext, s: &[i16]) -> Result<MirSharedKey, MirError> {
+    let mut shared = MirSharedKey { key: [0u8; SHARED_LEN] };
+    decaps_bytes_with_secret(ct, s, &[], &mut shared.key)?;
+    Ok(shared)
+}
+
+fn decaps_bytes_with_secret(ct: &MirCiphertext, s: &[i16], context: &[u8], out: &mut [u8]) -> Result<(), MirError> {
+    decaps_core(&ct.u, &ct.mask, s, context, out)
+}
+
+/// Shared body of all decapsulation entry points, on any block count.
+/// Errors only come from the kernels; a vault built with `new` from a
+/// wrongly sized vector is the realistic source.
+fn decaps_core(u: &[u8], mask: &[u8; N / 8], s: &[i16], context: &[u8], out: &mut [u8]) -> Result<(), MirError> {
+    let mut v_prime = ScratchGuard::new([0u8; N]);
+    poly::try_inner_product(u, s, &mut *v_prime)?;
+
+    // Mask filtering (Reconciliation)
+    let widx = ct::popcount(mask);
+
+    // KDF: SHAKE-256 (Same Domain, length-prefixed)
+    derive_shared_bytes(&v_prime, mask, widx, context, out);
+    Ok(())
+}
+
+/* === [12. Configured Exchange] === */
+
+/// One set of exchange options, applied identically on both sides.
+///
+/// The free functions (`encaps_with_rng`, `decaps`, ...) are the default
+/// configuration: empty context, no entropy floor, `SHARED_LEN` bytes.
+/// Sender and recipient must use equal configurations; a mismatched context
+/// or length silently yields unrelated keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MirConfig<'a> {
+    context: &'a [u8],
+    min_entropy: u16,
+    shared_len: usize,
+}
+
+impl<'a> MirConfig<'a> {
+    /// Largest accepted `shared_len`: two `SHARED_LEN` keys (e.g. one for
+    /// encryption, one for authentication). The key never carries more than
+    /// `N` bits of entropy, so longer outputs only stretch it further.
+    pub const MAX_SHARED_LEN: usize = 2 * SHARED_LEN;
+
+    /// The default configuration, equivalent to the free functions.
+    pub const fn new() -> Self {
+        MirConfig { context: &[], min_entropy: 0, shared_len: SHARED_LEN }
+    }
+
+    /// Binds the shared key to an application context (protocol name, version, ...).
+    ///
+    /// The context is length-prefixed under its own domain tag, so distinct
+    /// contexts never collide with each other or with the empty one.
+    pub const fn context(mut self, context: &'a [u8]) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Rejects exchanges whose ciphertext carries fewer than `min_cnt` safe bits.
+    pub const fn min_entropy(mut self, min_cnt: u16) -> Self {
+        self.min_entropy = min_cnt;
+        self
+    }
+
+    /// Sets the shared-key length in bytes (see `encaps_bytes`).
+    ///
+    /// Lengths of 0 or above `MAX_SHARED_LEN` make every exchange under this
+    /// configuration fail with `MirError::BadLength`.
+    pub const fn shared_len(mut self, len: usize) -> Self {
+        self.shared_len = len;
+        self
+    }
+
+    /// Encapsulates under this configuration.
+    ///
+    /// # Errors
+    /// `MirError::BadLength` if `shared_len` is out of range, `MirError::Rng`
+    /// if `rng` fails, and `MirError::InsufficientEntropy` if the
+    /// ciphertext's `cnt` is below the configured minimum. Retrying draws
+    /// fresh coins.
+    #[cfg(feature = "alloc")]
+    pub fn encaps<R: RngCore + CryptoRng>(
+        &self,
+        pk: &MirPubkey,
+        rng: &mut R,
+    ) -> Result<(MirCiphertext, Zeroizing<Vec<u8>>), MirError> {
+        self.check_shared_len()?;
+        let mut key = Zeroizing::new(vec![0u8; self.shared_len]);
+        let ct = self.encaps_bytes(pk, rng, &mut key)?;
+        Ok((ct, key))
+    }
+
+    /// `encaps` into a caller-owned `key_out` of exactly `shared_len` bytes,
+    /// for builds without an allocator.
+    ///
+    /// # Errors
+    /// `MirError::BadLength` if `key_out` has the wrong length,
+    /// `MirError::Rng` if `rng` fails, otherwise as `encaps`. After an `Rng`
+    /// or entropy error `key_out` is left zeroed.
+    pub fn encaps_bytes<R: RngCore + CryptoRng>(
+        &self,
+        pk: &MirPubkey,
+        rng: &mut R,
+        key_out: &mut [u8],
+    ) -> Result<MirCiphertext, MirError> {
+        self.check_shared_len()?;
+        if key_out.len() != self.shared_len {
+            return Err(MirError::BadLength);
+        }
+
+        let mut eph_seed = ScratchGuard::new([0u8; 32]);
+        if let Err(err) = try_fill(rng, &mut *eph_seed) {
+            key_out.zeroize();
+            return Err(err);
+        }
+
+        let mut r = ScratchGuard::new([0i16; K * N]);
+        let mut ct = MirCiphertext::default();
+        let res = encaps_core(pk, &poly::gen_matrix(&pk.seed), &eph_seed, &mut *r, &mut ct, self.context, key_out);
+
+        if res.is_err() || ct.cnt < self.min_entropy {
+            key_out.zeroize();
+            res?;
+            return Err(MirError::InsufficientEntropy);
+        }
+        Ok(ct)
+    }
+
+    /// Decapsulates under this configuration.
+    ///
+    /// # Errors
+    /// `MirError::BadLength` if `shared_len` is out of range, as
+    /// `MirCiphertext::check`, and `MirError::InsufficientEntropy` if `cnt`
+    /// is below the configured minimum.
+    #[cfg(feature = "alloc")]
+    pub fn decaps(&self, ct: &MirCiphertext, vault: &MirSecretVault) -> Result<Zeroizing<Vec<u8>>, MirError> {
+        self.check_shared_len()?;
+        let mut key = Zeroizing::new(vec![0u8; self.shared_len]);
+        self.decaps_bytes(ct, vault, &mut key)?;
+        Ok(key)
+    }
+
+    /// `decaps` into a caller-owned `key_out` of exactly `shared_len` bytes.
+    ///
+    /// # Errors
+    /// `MirError::BadLength` if `key_out` has the wrong length, otherwise as
+    /// `decaps`.
+    pub fn decaps_bytes(&self, ct: &MirCiphertext, vault: &MirSecretVault, key_out: &mut [u8]) -> Result<(), MirError> {
+        self.check_shared_len()?;
+        if key_out.len() != self.shared_len {
+            return Err(MirError::BadLength);
+        }
+        ct.check()?;
+        if ct.cnt < self.min_entropy {
+            return Err(MirError::InsufficientEntropy);
+        }
+
+        vault.access(|s| decaps_bytes_with_secret(ct, s, self.context, key_out))
+    }
+
+    /// Rejects a `shared_len` of 0 or above `MAX_SHARED_LEN`, before any
+    /// buffer of that length is allocated.
+    fn check_shared_len(&self) -> Result<(), MirError> {
+        if self.shared_len == 0 || self.shared_len > Self::MAX_SHARED_LEN {
+            return Err(MirError::BadLength);
+        }
+        Ok(())
+    }
+}
+
+impl Default for MirConfig<'_> {
+    fn default() -> Self {
+        MirConfig::new()
+    }
+}
+
+/* === [13. Parameter Sets] === */
+
+/// Const-generic key and ciphertext types over the rank `K`.
+mod generic;
+pub use generic::{
+    decaps_g, encaps_g, encaps_g_deterministic, encaps_g_with_rng, keygen_g, keygen_g_from_seed, keygen_g_with_rng,
+    MirCiphertextG, MirPubkeyG, MirSecretVaultG,
+};
+
+/* === [14. Serialization] === */
+
+/// Writes `MAGIC || FORMAT_VERSION` into the front of `out`.
+fn write_header(out: &mut [u8]) {
+    out[..MAGIC.len()].copy_from_slice(&MAGIC);
+    out[MAGIC.len()] = FORMAT_VERSION;
+}
+
+/// Validates the header at the front of `bytes` (at least `HEADER_BYTES` long).
+///
+/// # Errors
+/// `MirError::Malformed` if the magic is wrong and
+/// `MirError::UnsupportedVersion` if the version differs from `FORMAT_VERSION`.
+fn check_header(bytes: &[u8]) -> Result<(), MirError> {
+    if bytes[..MAGIC.len()] != MAGIC {
+        return Err(MirError::Malformed);
+    }
+    if bytes[MAGIC.len()] != FORMAT_VERSION {
+        return Err(MirError::UnsupportedVersion);
+    }
+    Ok(())
+}
+
+/// Rejects a decoded `cnt` no mask could produce.
+///
+/// # Errors
+/// `MirError::OutOfRange` if `cnt > N`.
+fn check_cnt(cnt: u16) -> Result<(), MirError> {
+    if cnt as usize > N {
+        return Err(MirError::OutOfRange);
+    }
+    Ok(())
+}
+
+/// Packs the low `bits` of each value of `src` into `out`, LSB first.
+///
+/// `out` must hold exactly `(src.len() * bits).div_ceil(8)` bytes. Unused
+/// bits of the last byte are left zero.
+fn pack_coeffs(src: &[u8], bits: u32, out: &mut [u8]) {
+    let mask = (1u32 << bits) - 1;
+    let mut acc: u32 = 0;
+    let mut held = 0;
+    let mut o = 0;
+    for &v in src {
+        acc |= (v as u32 & mask) << held;
+        held += bits;
+        while held >= 8 {
+            out[o] = acc as u8;
+            o += 1;
+            acc >>= 8;
+            held -= 8;
+        }
+    }
+    if held > 0 {
+        out[o] = acc as u8;
+    }
+}
+
+/// Inverse of `pack_coeffs`: fills `out` with `bits`-bit values from `src`.
+///
+/// Reads only the bytes `pack_coeffs` writes, so a short final byte is taken
+/// as-is and never read past.
+///
+/// # Errors
+/// `MirError::Malformed` if the padding bits of the last byte are not zero.
+fn unpack_coeffs(src: &[u8], bits: u32, out: &mut [u8]) -> Result<(), MirError> {
+    let mask = (1u32 << bits) - 1;
+    let mut acc: u32 = 0;
+    let mut held = 0;
+    let mut i = 0;
+    for v in out.iter_mut() {
+        while held < bits {
+            acc |= (src[i] as u32) << held;
+            i += 1;
+            held += 8;
+        }
+        *v = (acc & mask) as u8;
+        acc >>= bits;
+        held -= bits;
+    }
+    if acc != 0 {
+        return Err(MirError::Malformed);
+    }
+    Ok(())
+}
+
+impl MirPubkey {
+    /// Serializes the public key as `MAGIC || FORMAT_VERSION || seed || b`.
+    pub fn to_bytes(&self) -> [u8; PUBKEY_BYTES] {
+        let mut out = [0u8; PUBKEY_BYTES];
+        let _ = generic::write_pubkey(&self.seed, &self.b, &mut out);
+        out
+    }
+
+    /// Parses a public key produced by `to_bytes`.
+    ///
+    /// # Errors
+    /// `MirError::BadLength` if `bytes` is not exactly `PUBKEY_BYTES` long,
+    /// then as `check_header`: `MirError::Malformed` for a wrong magic and
+    /// `MirError::UnsupportedVersion` for a blob from an incompatible build.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MirError> {
+        let mut pk = MirPubkey::default();
+        generic::read_pubkey(bytes, &mut pk.seed, &mut pk.b)?;
+        Ok(pk)
+    }
+
+    /// Serializes the public key with `b` packed at `B_BITS` bits per
+    /// coefficient (LSB first, zero padding in the last byte).
+    ///
+    /// Every key from `keygen` round-trips exactly; a `b` set through
+    /// `from_parts` with values of `2^B_BITS` or more loses the excess bits.
+    pub fn to_bytes_compressed(&self) -> [u8; PUBKEY_COMPRESSED_BYTES] {
+        const B: usize = HEADER_BYTES + SEED_LEN;
+        let mut out = [0u8; PUBKEY_COMPRESSED_BYTES];
+        write_header(&mut out);
+        out[HEADER_BYTES..B].copy_from_slice(&self.seed);
+        pack_coeffs(&self.b, B_BITS, &mut out[B..]);
+        out
+    }
+
+    /// Parses a public key produced by `to_bytes_compressed`, restoring the
+    /// full byte-per-coefficient `b`.
+    ///
+    /// # Errors
+    /// `MirError::BadLength` if `bytes` is not exactly `PUBKEY_COMPRESSED_BYTES`
+    /// long, header errors as for `from_bytes`, and `MirError::Malformed` if
+    /// the padding bits are not zero.
+    pub fn from_bytes_compressed(bytes: &[u8]) -> Result<Self, MirError> {
+        const B: usize = HEADER_BYTES + SEED_LEN;
+        if bytes.len() != PUBKEY_COMPRESSED_BYTES {
+            return Err(MirError::BadLength);
+        }
+        check_header(bytes)?;
+
+        let mut pk = MirPubkey::default();
+        pk.seed.copy_from_slice(&bytes[HEADER_BYTES..B]);
+        unpack_coeffs(&bytes[B..], B_BITS, &mut pk.b)?;
+        Ok(pk)
+    }
+
+    /// Streams the `to_bytes` encoding (header, `seed`, then `b`) into `w`
+    /// without building the full buffer (`std` feature).
+    #[cfg(feature = "std")]
+    pub fn write_to<W: std::io::Write>(&self, w: &mut W) -> std::io::Result<()> {
+        let mut header = [0u8; HEADER_BYTES];
+        write_header(&mut header);
+        w.write_all(&header)?;
+        w.write_all(&self.seed)?;
+        w.write_all(&self.b)
+    }
+
+    /// Reads a public key written by `write_to` (or `to_bytes`) from `r`,
+    /// filling the fields in place (`std` feature).
+    ///
+    /// # Errors
+    /// Any I/O error from `r`, including `UnexpectedEof` on a short stream,
+    /// and `InvalidData` if the header is rejected.
+    #[cfg(feature = "std")]
+    pub fn read_from<R: std::io::Read>(r: &mut R) -> std::io::Result<Self> {
+        let mut header = [0u8; HEADER_BYTES];
+        r.read_exact(&mut header)?;
+        if let Err(err) = check_header(&header) {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err));
+        }
+
+        let mut pk = MirPubkey::default();
+        r.read_exact(&mut pk.seed)?;
+        r.read_exact(&mut pk.b)?;
+        Ok(pk)
+    }
+}
+
+impl MirCiphertext {
+    /// Serializes the ciphertext as `MAGIC || FORMAT_VERSION || u || mask || cnt`
+    /// (`cnt` as 2-byte LE).
+    pub fn to_bytes(&self) -> [u8; CIPHERTEXT_BYTES] {
+        let mut out = [0u8; CIPHERTEXT_BYTES];
+        let _ = generic::write_ciphertext(&self.u, &self.mask, self.cnt, &mut out);
+        out
+    }
+
+    /// Parses a ciphertext produced by `to_bytes`.
+    ///
+    /// # Errors
+    /// `MirError::BadLength` if `bytes` is not exactly `CIPHERTEXT_BYTES` long,
+    /// then header errors as for `MirPubkey::from_bytes`, and
+    /// `MirError::OutOfRange` if `cnt > N`. Agreement of `cnt` with the mask
+    /// is left to `check`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MirError> {
+        let mut ct = MirCiphertext::default();
+        generic::read_ciphertext(bytes, &mut ct.u, &mut ct.mask, &mut ct.cnt)?;
+        Ok(ct)
+    }
+
+    /// Serializes the ciphertext followed by its `encaps_mac` tag (`mac` feature).
+    #[cfg(feature = "mac")]
+    pub fn to_bytes_with_mac(&self, tag: &[u8; MAC_LEN]) -> [u8; CIPHERTEXT_MAC_BYTES] {
+        let mut out = [0u8; CIPHERTEXT_MAC_BYTES];
+        out[..CIPHERTEXT_BYTES].copy_from_slice(&self.to_bytes());
+        out[CIPHERTEXT_BYTES..].copy_from_slice(tag);
+        out
+    }
+
+    /// Parses a tagged ciphertext produced by `to_bytes_with_mac`; the tag is
+    /// checked by `decaps_mac` (`mac` feature).
+    ///
+    /// # Errors
+    /// `MirError::BadLength` if `bytes` is not exactly `CIPHERTEXT_MAC_BYTES`
+    /// long, then as `from_bytes`.
+    #[cfg(feature = "mac")]
+    pub fn from_bytes_with_mac(bytes: &[u8]) -> Result<(Self, [u8; MAC_LEN]), MirError> {
+        if bytes.len() != CIPHERTEXT_MAC_BYTES {
+            return Err(MirError::BadLength);
+        }
+        let ct = MirCiphertext::from_bytes(&bytes[..CIPHERTEXT_BYTES])?;
+        let mut tag = [0u8; MAC_LEN];
+        tag.copy_from_slice(&bytes[CIPHERTEXT_BYTES..]);
+        Ok((ct, tag))
+    }
+
+    /// Reads only the trailing `cnt` of a `to_bytes` encoding, so degenerate
+    /// ciphertexts (too few reconciled bits) can be dropped before a full
+    /// parse. The value is not checked against the mask; `is_valid` does that.
+    ///
+    /// # Errors
+    /// As `from_bytes`: `MirError::BadLength` for a truncated or oversized
+    /// blob, then the header errors, then `MirError::OutOfRange`.
+    pub fn peek_cnt(bytes: &[u8]) -> Result<u16, MirError> {
+        if bytes.len() != CIPHERTEXT_BYTES {
+            return Err(MirError::BadLength);
+        }
+        check_header(bytes)?;
+        let cnt = u16::from_le_bytes([bytes[CIPHERTEXT_BYTES - 2], bytes[CIPHERTEXT_BYTES - 1]]);
+        check_cnt(cnt)?;
+        Ok(cnt)
+    }
+}
+
+/// Deterministic CBOR encoding (`cbor` feature).
+#[cfg(feature = "cbor")]
+mod cbor;
+
+/// Checksummed z-base-32 text encoding.
+mod zbase32;
+
+/* === [15. Session Transcript] === */
+
+/// Running SHAKE-256 transcript for binding several exchanges into one key.
+///
+/// Every item is framed as `kind (1 byte) || len (8-byte LE) || data`, so
+/// item boundaries are unambiguous and a shared key can never be confused
+/// with an absorbed message carrying the same bytes. The sponge state holds
+/// mixed-in keys and cannot be scrubbed (see `ScratchGuard`); drop the
+/// transcript as soon as the session keys are derived.
+#[derive(Clone)]
+pub struct MirTranscript {
+    state: Shake256,
+}
+
+const TRANSCRIPT_DATA: u8 = 0x00;
+const TRANSCRIPT_SHARED: u8 = 0x01;
+
+impl MirTranscript {
+    /// Starts an empty transcript.
+    pub fn new() -> Self {
+        let mut state = Shake256::default();
+        state.update(&[domains::TRANSCRIPT]);
+        MirTranscript { state }
+    }
+
+    /// Absorbs a handshake message (or any public context).
+    pub fn absorb(&mut self, data: &[u8]) {
+        self.frame(TRANSCRIPT_DATA, data);
+    }
+
+    /// Mixes a Mirletis shared key into the transcript.
+    pub fn mix_shared(&mut self, key: &MirSharedKey) {
+        self.frame(TRANSCRIPT_SHARED, &key.key);
+    }
+
+    /// Squeezes `out.len()` bytes of session key material.
+    pub fn finalize_into(self, out: &mut [u8]) {
+        self.state.finalize_xof().read(out);
+    }
+
+    fn frame(&mut self, kind: u8, data: &[u8]) {
+        self.state.update(&[kind]);
+        self.state.update(&(data.len() as u64).to_le_bytes());
+        self.state.update(data);
+    }
+}
+
+impl Default for MirTranscript {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/* === [16. Self Test & Verification] === */
+
+/// Performs a self-test of the Key Encapsulation Mechanism.
+/// Returns `true` if Alice and Bob derive the same shared secret.
+pub fn self_test() -> bool {
+    let (pk, vault) = keygen();
+    let (ct, key_bob) = encaps(&pk);
+    let key_alice = decaps(&ct, &vault);
+
+    ct::eq_slice(&key_alice.key, &key_bob.key)
+}
+
+/// Allocation-free known-answer self-test for `no_std` firmware (power-on tests).
+///
+/// Runs the exchange `keygen` would run for the 64-byte master `seed`
+/// (public seed, then secret seed), encapsulating with the `encaps_det`
+/// coins for `message = seed`. `A` is streamed block by block and every
+/// buffer is a fixed-size stack array, so this never allocates, whichever
+/// secret storage the build uses.
+///
+/// Returns `true` if both sides derive the same shared secret.
+pub fn self_test_embedded(seed: &[u8; 64]) -> bool {
+    // Key generation
+    let mut pk = MirPubkey::default();
+    pk.seed.copy_from_slice(&seed[..32]);
+    let mut s = ScratchGuard::new([0i16; K * N]);
+    gen_secret_from_seed(&seed[32..], &mut *s);
+    poly::matvec_streamed(&pk.seed, &*s, &mut pk.b);
+
+    // Encapsulation
+    let mut coins = ScratchGuard::new([0u8; 32]);
+    derive_det_coins(&pk, seed, &mut coins);
+    let mut r = ScratchGuard::new([0i16; K * N]);
+    gen_secret_from_seed(&*coins, &mut *r);
+    let mut ct = MirCiphertext::default();
+    poly::matvec_transpose_streamed(&pk.seed, &*r, &mut ct.u);
+    let mut key_bob = ScratchGuard::new([0u8; SHARED_LEN]);
+    ct.cnt = poly::unwrap_kernel(encaps_finish(&pk.b, &*r, &mut ct.mask, &[], &mut *key_bob));
+
+    // Decapsulation
+    let mut key_alice = ScratchGuard::new([0u8; SHARED_LEN]);
+    poly::unwrap_kernel(decaps_bytes_with_secret(&ct, &*s, &[], &mut *key_alice));
+
+    ct::eq_slice(&*key_alice, &*key_bob)
+}
+
+/// Stage-by-stage vectors for the arithmetic core (`kat` feature). The
+/// vectors assume the default SHAKE-256 expansion and power-of-two modulus.
+#[cfg(all(test, feature = "kat", not(any(feature = "aes-expand", feature = "prime-modulus", feature = "padded-kdf"))))]
+mod kat;
+
+/// Times `iters` rounds of keygen/encaps/decaps (`bench` feature, std only).
+///
+/// # Returns
+/// The mean duration of a single (`keygen`, `encaps`, `decaps`) call.
+#[cfg(feature = "bench")]
+pub fn bench_roundtrip(iters: usize) -> (core::time::Duration, core::time::Duration, core::time::Duration) {
+    use core::time::Duration;
+    use std::time::Instant;
+
+    let mut t_keygen = Duration::ZERO;
+    let mut t_encaps = Duration::ZERO;
+    let mut t_decaps = Duration::ZERO;
+
+    let mut i = 0;
+    while i < iters {
+        let start = Instant::now();
+        let (pk, vault) = keygen();
+        t_keygen += start.elapsed();
+
+        let start = Instant::now();
+        let (ct, _key_bob) = encaps(&pk);
+        t_encaps += start.elapsed();
+
+        let start = Instant::now();
+        let _key_alice = decaps(&ct, &vault);
+        t_decaps += start.elapsed();
+
+        i += 1;
+    }
+
+    let div = if iters == 0 { 1 } else { iters as u32 };
+    (t_keygen / div, t_encaps / div, t_decaps / div)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic CSPRNG for reproducible tests (SHAKE-256 keystream).
+    struct TestRng(Shake256Reader);
+
+    impl TestRng {
+        fn new(seed: u8) -> Self {
+            TestRng(mir_shake_reader(&[seed], 0xFF))
+        }
+    }
+
+    impl RngCore for TestRng {
+        fn next_u32(&mut self) -> u32 {
+            let mut buf = [0u8; 4];
+            self.fill_bytes(&mut buf);
+            u32::from_le_bytes(buf)
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            let mut buf = [0u8; 8];
+            self.fill_bytes(&mut buf);
+            u64::from_le_bytes(buf)
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            self.0.read(dest);
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    impl CryptoRng for TestRng {}
+
+    #[test]
+    fn test_correctness() {
+        assert!(self_test(), "Key exchange failed");
+    }
+
+    #[test]
+    fn test_stress_repeat() {
+        for _ in 0..100 {
+            assert!(self_test());
+        }
+    }
+
+    #[test]
+    fn test_self_test_embedded() {
+        for seed in 0u8..4 {
+            assert!(self_test_embedded(&[seed; 64]));
+        }
+
+        // The streamed products are exactly the expanded ones
+        let seed = [0x46u8; 64];
+        let (pk, vault) = keygen_from_master(&seed);
+        let mut b = [0u8; K * N];
+        vault.access(|s| poly::matvec_streamed(&pk.seed, s, &mut b));
+        assert_eq!(b[..], pk.b[..]);
+
+        let (ct, _) = encaps_det(&pk, &seed);
+        let mut coins = [0u8; 32];
+        derive_det_coins(&pk, &seed, &mut coins);
+        let mut r = [0i16; K * N];
+        gen_secret_from_seed(&coins, &mut r);
+        let mut u = [0u8; K * N];
+        poly::matvec_transpose_streamed(&pk.seed, &r, &mut u);
+        assert_eq!(u[..], ct.u[..]);
+    }
+
+    #[test]
+    fn test_constant_time_ops() {
         assert_eq!(ct::ternary(0), -1);
         assert_eq!(ct::ternary(1), 0);
         assert_eq!(ct::ternary(2), 1);
@@ -471,5 +3399,1525 @@ mod tests {
         assert_eq!(ct::safe_zone(32), 1);
         assert_eq!(ct::safe_zone(64), 0);
     }
+
+    #[test]
+    fn test_params() {
+        const P: Params = params();
+        assert_eq!(P.n * P.k, K * N);
+        assert_eq!(P.q, Q);
+        assert_eq!(P.q_mask, Q_MASK);
+        assert_eq!(P.shift, SHIFT);
+        assert_eq!(P.seed_len, SEED_LEN);
+        assert_eq!(P.shared_len, SHARED_LEN);
+
+        // Usable as array lengths, and in sync with the serializers
+        let pk_buf = [0u8; PUBKEY_BYTES];
+        let ct_buf = [0u8; CIPHERTEXT_BYTES];
+        assert_eq!(pk_buf.len(), MirPubkey::default().to_bytes().len());
+        assert_eq!(ct_buf.len(), MirCiphertext::default().to_bytes().len());
+        assert_eq!(SECRET_SEED_BYTES, MirSecretSeed::from_bytes([0u8; 32]).as_bytes().len());
+    }
+
+    #[test]
+    fn test_enabled_features() {
+        let enabled = enabled_features();
+        assert_eq!(enabled.contains(&"std"), cfg!(feature = "std"));
+        assert_eq!(enabled.contains(&"heapless"), cfg!(feature = "heapless"));
+        assert_eq!(enabled.contains(&"prime-modulus"), cfg!(feature = "prime-modulus"));
+        assert!(enabled.iter().all(|name| FEATURES.contains(&(*name, true))));
+        assert_eq!(enabled.len(), FEATURES.iter().filter(|f| f.1).count());
+
+        // Every feature declared in the manifest is listed
+        let manifest = include_str!("../Cargo.toml");
+        let section = manifest.split("[features]").nth(1).unwrap().split("\n[").next().unwrap();
+        for line in section.lines().filter(|l| l.contains(" = [") && !l.starts_with("default")) {
+            let name = line.split(' ').next().unwrap();
+            assert!(FEATURES.iter().any(|f| f.0 == name), "{} missing from FEATURES", name);
+        }
+    }
+
+    #[test]
+    fn test_reduce_matches_modulus() {
+        let bound = K as i32 * Q;
+        let mut x = -bound;
+        while x <= bound {
+            assert_eq!(poly::reduce(x), x.rem_euclid(Q), "x = {}", x);
+            x += 1;
+        }
+        for &x in [-(Q << 8), i32::MAX - (Q << 8)].iter() {
+            assert_eq!(poly::reduce(x), x.rem_euclid(Q), "x = {}", x);
+        }
+
+        let matrix_a = poly::gen_matrix(&[0x5Au8; SEED_LEN]);
+        assert!(matrix_a.iter().all(|&c| (0..Q as i16).contains(&c)));
+    }
+
+    /// Batch reference for `derive_shared_bytes`: hashes an explicit reconciliation buffer.
+    #[cfg(not(feature = "padded-kdf"))]
+    fn kdf_batch(bits: &[u8]) -> [u8; SHARED_LEN] {
+        let mut input = [0u8; 8 + 2 + N];
+        input[..8].copy_from_slice(&(SHARED_LEN as u64).to_le_bytes());
+        input[8..10].copy_from_slice(&(bits.len() as u16).to_le_bytes());
+        input[10..10 + bits.len()].copy_from_slice(bits);
+
+        let mut out = [0u8; SHARED_LEN];
+        mir_shake_reader(&input[..10 + bits.len()], domains::HASH).read(&mut out);
+        out
+    }
+
+    /// Builds `(v, mask)` whose selected bits are exactly `bits`, one every `stride` slots.
+    fn kdf_inputs(bits: &[u8], stride: usize) -> ([u8; N], [u8; N / 8]) {
+        let mut v = [0u8; N];
+        let mut mask = [0u8; N / 8];
+        for (i, &bit) in bits.iter().enumerate() {
+            let idx = i * stride;
+            v[idx] = 32 + 64 * bit + (idx as u8 & 1);
+            ct::bit_set(&mut mask, idx, 1);
+        }
+        (v, mask)
+    }
+
+    #[cfg(not(feature = "padded-kdf"))]
+    #[test]
+    fn test_kdf_stream_matches_batch() {
+        for (len, stride) in [(0usize, 1usize), (3, 1), (50, 5), (128, 2), (N, 1)] {
+            let mut bits = [0u8; N];
+            for (i, bit) in bits.iter_mut().enumerate() {
+                *bit = ((i * 7) % 3 == 0) as u8;
+            }
+            let (v, mask) = kdf_inputs(&bits[..len], stride);
+            let mut out = [0u8; SHARED_LEN];
+            derive_shared_bytes(&v, &mask, len as u16, &[], &mut out);
+            assert_eq!(out, kdf_batch(&bits[..len]), "len = {}", len);
+        }
+    }
+
+    #[cfg(not(feature = "padded-kdf"))]
+    #[test]
+    fn test_kdf_commits_length() {
+        assert_ne!(kdf_batch(&[1, 0, 1]), kdf_batch(&[1, 0, 1, 0]));
+
+        let mut raw = [0u8; 32];
+        mir_shake_reader(&[1, 0, 1], domains::HASH).read(&mut raw);
+        assert_ne!(kdf_batch(&[1, 0, 1]), raw);
+
+        // The output length is committed too: no output is a prefix of a longer one
+        let (v, mask) = kdf_inputs(&[1, 0, 1], 1);
+        let (mut short, mut long) = ([0u8; 32], [0u8; 48]);
+        derive_shared_bytes(&v, &mask, 3, &[], &mut short);
+        derive_shared_bytes(&v, &mask, 3, &[], &mut long);
+        assert_ne!(short[..], long[..32]);
+    }
+
+    #[cfg(feature = "padded-kdf")]
+    #[test]
+    fn test_padded_kdf() {
+        let mut bits = [0u8; N];
+        for (i, bit) in bits.iter_mut().enumerate() {
+            *bit = ((i * 7) % 3 == 0) as u8;
+        }
+        let (v, mask) = kdf_inputs(&bits[..50], 5);
+
+        // Reference: the whole N-byte buffer, zero wherever mask is clear
+        let mut input = [0u8; 8 + 8 + 2 + N / 8 + N];
+        input[..8].copy_from_slice(&(SHARED_LEN as u64).to_le_bytes());
+        input[16..18].copy_from_slice(&50u16.to_le_bytes());
+        input[18..18 + N / 8].copy_from_slice(&mask);
+        for i in 0..50 {
+            input[18 + N / 8 + 5 * i] = bits[i];
+        }
+        let mut want = [0u8; SHARED_LEN];
+        mir_shake_reader(&input, domains::HASH_PADDED).read(&mut want);
+
+        let mut out = [0u8; SHARED_LEN];
+        derive_shared_bytes(&v, &mask, 50, &[], &mut out);
+        assert_eq!(out, want);
+
+        // Unselected coefficients do not reach the hash, whatever they hold
+        let mut noisy = v;
+        noisy[1] = 0xFF;
+        let mut out2 = [0u8; SHARED_LEN];
+        derive_shared_bytes(&noisy, &mask, 50, &[], &mut out2);
+        assert_eq!(out2, out);
+
+        // A selected zero and an unselected position differ through the mask
+        let (v0, mask0) = kdf_inputs(&[1, 0], 1);
+        let (v1, mask1) = kdf_inputs(&[1], 1);
+        let (mut k0, mut k1) = ([0u8; SHARED_LEN], [0u8; SHARED_LEN]);
+        derive_shared_bytes(&v0, &mask0, 2, &[], &mut k0);
+        derive_shared_bytes(&v1, &mask1, 2, &[], &mut k1);
+        assert_ne!(k0, k1);
+
+        let (pk, vault) = keygen_from_seed(&[0x31u8; 32]).unwrap();
+        let (ct, key) = encaps_deterministic(&pk, &[0x32u8; 32]);
+        assert_eq!(decaps(&ct, &vault).key, key.key);
+    }
+
+    #[test]
+    fn test_encaps_bytes() {
+        let (pk, vault) = keygen();
+        let mut bob = [0u8; 48];
+        let ct = encaps_bytes_with_rng(&pk, &mut TestRng::new(12), &mut bob);
+        let mut alice = [0u8; 48];
+        decaps_bytes(&ct, &vault, &mut alice);
+        assert_eq!(alice, bob);
+
+        // 32 bytes is exactly `MirSharedKey`
+        let (ct, key) = encaps_with_rng(&pk, &mut TestRng::new(12));
+        let mut short = [0u8; SHARED_LEN];
+        decaps_bytes(&ct, &vault, &mut short);
+        assert_eq!(short, key.key);
+        assert_ne!(short[..], bob[..32]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_mir_config() {
+        let (pk, vault) = keygen();
+
+        // The default config is the free functions
+        let (ct, key) = MirConfig::default().encaps(&pk, &mut TestRng::new(21)).unwrap();
+        let (ct2, key2) = encaps_with_rng(&pk, &mut TestRng::new(21));
+        assert_eq!(ct.to_bytes(), ct2.to_bytes());
+        assert_eq!(key[..], key2.key);
+        assert_eq!(MirConfig::new().decaps(&ct, &vault).unwrap()[..], key2.key);
+
+        // Context and length are bound into the key on both sides
+        let config = MirConfig::new().context(b"app/v1").shared_len(48);
+        let (ct, key) = config.encaps(&pk, &mut TestRng::new(22)).unwrap();
+        assert_eq!(key.len(), 48);
+        assert_eq!(config.decaps(&ct, &vault).unwrap(), key);
+        let other = config.context(b"app/v2").decaps(&ct, &vault).unwrap();
+        assert_ne!(other, key);
+        let mut plain = [0u8; 48];
+        decaps_bytes(&ct, &vault, &mut plain);
+        assert_ne!(plain[..], key[..]);
+
+        // The entropy floor applies to both sides
+        let strict = MirConfig::new().min_entropy(ct.cnt + 1);
+        assert!(matches!(strict.encaps(&pk, &mut TestRng::new(22)), Err(MirError::InsufficientEntropy)));
+        assert_eq!(strict.decaps(&ct, &vault).err(), Some(MirError::InsufficientEntropy));
+    }
+
+    #[test]
+    fn test_mir_config_bytes() {
+        let (pk, vault) = keygen();
+        let config = MirConfig::new().context(b"app/v1").shared_len(48);
+
+        let mut key = [0u8; 48];
+        let ct = config.encaps_bytes(&pk, &mut TestRng::new(23), &mut key).unwrap();
+        let mut key2 = [0u8; 48];
+        config.decaps_bytes(&ct, &vault, &mut key2).unwrap();
+        assert_eq!(key, key2);
+
+        // The buffer must match the configured length
+        let mut short = [0u8; 32];
+        assert_eq!(config.encaps_bytes(&pk, &mut TestRng::new(23), &mut short).err(), Some(MirError::BadLength));
+        assert_eq!(config.decaps_bytes(&ct, &vault, &mut short).err(), Some(MirError::BadLength));
+
+        // A rejected exchange leaves no key material behind
+        let strict = config.min_entropy(ct.cnt + 1);
+        let mut rejected = [0xAAu8; 48];
+        assert_eq!(
+            strict.encaps_bytes(&pk, &mut TestRng::new(23), &mut rejected).err(),
+            Some(MirError::InsufficientEntropy)
+        );
+        assert_eq!(rejected, [0u8; 48]);
+    }
+
+    #[test]
+    fn test_mir_config_shared_len_bounds() {
+        let (pk, vault) = keygen();
+        let max = MirConfig::MAX_SHARED_LEN;
+
+        let mut key = [0u8; MirConfig::MAX_SHARED_LEN + 1];
+        let ct = MirConfig::new().shared_len(max).encaps_bytes(&pk, &mut TestRng::new(24), &mut key[..max]).unwrap();
+        let mut key2 = [0u8; MirConfig::MAX_SHARED_LEN];
+        MirConfig::new().shared_len(max).decaps_bytes(&ct, &vault, &mut key2).unwrap();
+        assert_eq!(key[..max], key2);
+
+        // Out-of-range lengths fail even when the buffer matches them
+        for len in [0, max + 1] {
+            let config = MirConfig::new().shared_len(len);
+            let out = &mut key[..len];
+            assert_eq!(config.encaps_bytes(&pk, &mut TestRng::new(24), out).err(), Some(MirError::BadLength));
+            assert_eq!(config.decaps_bytes(&ct, &vault, out).err(), Some(MirError::BadLength));
+        }
+
+        // ... and before the heap-backed variants allocate
+        #[cfg(feature = "alloc")]
+        {
+            let huge = MirConfig::new().shared_len(usize::MAX);
+            assert!(matches!(huge.encaps(&pk, &mut TestRng::new(24)), Err(MirError::BadLength)));
+            assert_eq!(huge.decaps(&ct, &vault).err(), Some(MirError::BadLength));
+        }
+    }
+
+    #[test]
+    fn test_safe_zone_boundaries() {
+        for &c in ct::ZONE_CENTERS.iter() {
+            let t = ct::ZONE_THRESHOLD;
+            assert_eq!(ct::safe_zone(c as u8), 1);
+            assert_eq!(ct::safe_zone((c - t + 1) as u8), 1);
+            assert_eq!(ct::safe_zone((c + t - 1) as u8), 1);
+            assert_eq!(ct::safe_zone((c - t) as u8), 0);
+            assert_eq!(ct::safe_zone((c + t) as u8), 0);
+        }
+    }
+
+    #[test]
+    fn test_shared_key_combine() {
+        let (pk_a, vault_a) = keygen_with_rng(&mut TestRng::new(15));
+        let (pk_b, vault_b) = keygen_with_rng(&mut TestRng::new(16));
+
+        // Each side encapsulates to the other
+        let (ct_to_a, k1_b) = encaps_with_rng(&pk_a, &mut TestRng::new(17));
+        let (ct_to_b, k2_a) = encaps_with_rng(&pk_b, &mut TestRng::new(18));
+        let k1_a = decaps(&ct_to_a, &vault_a);
+        let k2_b = decaps(&ct_to_b, &vault_b);
+
+        let on_a = k1_a.combine(&k2_a, b"transcript");
+        let on_b = k2_b.combine(&k1_b, b"transcript");
+        assert_eq!(on_a.key, on_b.key);
+        assert_ne!(on_a.key, k1_a.combine(&k2_a, b"other").key);
+        assert_ne!(on_a.key, k1_a.key);
+    }
+
+    #[test]
+    fn test_encaps_with_info() {
+        let (pk, vault) = keygen();
+        let (ct, key_bob, info) = encaps_with_info(&pk);
+        assert_eq!(info.safe_bits, ct.cnt);
+        assert!(info.safe_bits as usize <= N);
+        assert_eq!(decaps(&ct, &vault).key, key_bob.key);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_decaps_batch_matches_serial() {
+        let (pk, vault) = keygen();
+        let mut cts = Vec::new();
+        let mut keys = Vec::new();
+        for _ in 0..8 {
+            let (ct, key) = encaps(&pk);
+            cts.push(ct);
+            keys.push(key);
+        }
+
+        let batch = decaps_batch(&cts, &vault);
+        assert_eq!(batch.len(), cts.len());
+        for i in 0..cts.len() {
+            assert_eq!(batch[i].key, decaps(&cts[i], &vault).key);
+            assert_eq!(batch[i].key, keys[i].key);
+        }
+
+        #[cfg(feature = "rayon")]
+        {
+            let par = decaps_batch_par(&cts, &vault);
+            for i in 0..cts.len() {
+                assert_eq!(par[i].key, batch[i].key);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decaps_batch_into() {
+        let (pk, vault) = keygen();
+        let pairs: [_; 4] = core::array::from_fn(|_| encaps(&pk));
+        let cts = pairs.each_ref().map(|(ct, _)| *ct);
+
+        let mut out: [MirSharedKey; 4] = core::array::from_fn(|_| MirSharedKey { key: [0u8; SHARED_LEN] });
+        decaps_batch_into(&cts, &vault, &mut out).unwrap();
+        for (key, (_, expected)) in out.iter().zip(pairs.iter()) {
+            assert_eq!(key.key, expected.key);
+        }
+        assert_eq!(decaps_batch_into(&cts, &vault, &mut out[1..]).err(), Some(MirError::BadLength));
+    }
+
+    #[test]
+    fn test_keygen_from_seed() {
+        let seed = [0x42u8; MIN_SEED_LEN];
+        let (pk1, vault) = keygen_from_seed(&seed).unwrap();
+        let (pk2, _) = keygen_from_seed(&seed).unwrap();
+        assert_eq!(pk1.seed, pk2.seed);
+        assert_eq!(pk1.b[..], pk2.b[..]);
+
+        let (ct, key_bob) = encaps(&pk1);
+        assert_eq!(decaps(&ct, &vault).key, key_bob.key);
+
+        let (pk3, _) = keygen_from_seed(&[0x42u8; 64]).unwrap();
+        assert_ne!(pk1.seed, pk3.seed);
+
+        assert_eq!(keygen_from_seed(&seed[..MIN_SEED_LEN - 1]).err(), Some(MirError::BadLength));
+        assert_eq!(keygen_from_seed(&[]).err(), Some(MirError::BadLength));
+    }
+
+    #[test]
+    fn test_keygen_indexed() {
+        let master = [0x48u8; 32];
+        let (pk0, vault0) = keygen_indexed(&master, 0).unwrap();
+        assert_eq!(keygen_indexed(&master, 0).unwrap().0.to_bytes(), pk0.to_bytes());
+
+        let (ct, key_bob) = encaps(&pk0);
+        assert_eq!(decaps(&ct, &vault0).key, key_bob.key);
+
+        // Other indices, other masters, and the plain seed path all diverge
+        assert_ne!(keygen_indexed(&master, 1).unwrap().0.seed, pk0.seed);
+        assert_ne!(keygen_indexed(&master, 1 << 8).unwrap().0.seed, pk0.seed);
+        assert_ne!(keygen_indexed(&[0x49u8; 32], 0).unwrap().0.seed, pk0.seed);
+        assert_ne!(keygen_from_seed(&master).unwrap().0.seed, pk0.seed);
+
+        // Exactly `keygen_from_seed` on the per-index seed
+        let mut seed = [0u8; 32];
+        let mut hasher = Shake256::default();
+        hasher.update(&[domains::KEYGEN_INDEX]);
+        hasher.update(&master);
+        hasher.update(&0u32.to_le_bytes());
+        hasher.finalize_xof().read(&mut seed);
+        assert_eq!(keygen_from_seed(&seed).unwrap().0.to_bytes(), pk0.to_bytes());
+    }
+
+    #[test]
+    fn test_keygen_with_seeded_rng() {
+        let (pk1, vault) = keygen_with_rng(&mut TestRng::new(7));
+        let (pk2, _) = keygen_with_rng(&mut TestRng::new(7));
+        assert_eq!(pk1.seed, pk2.seed);
+        assert_eq!(pk1.b[..], pk2.b[..]);
+
+        let (ct, key_bob) = encaps_with_rng(&pk1, &mut TestRng::new(8));
+        let key_alice = decaps(&ct, &vault);
+        assert_eq!(key_alice.key, key_bob.key);
+    }
+
+    /// RNG whose source is permanently unavailable.
+    struct FailingRng;
+
+    impl RngCore for FailingRng {
+        fn next_u32(&mut self) -> u32 {
+            unreachable!()
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            unreachable!()
+        }
+
+        fn fill_bytes(&mut self, _dest: &mut [u8]) {
+            unreachable!()
+        }
+
+        fn try_fill_bytes(&mut self, _dest: &mut [u8]) -> Result<(), rand::Error> {
+            Err(rand::Error::from(core::num::NonZeroU32::new(rand::Error::CUSTOM_START).unwrap()))
+        }
+    }
+
+    impl CryptoRng for FailingRng {}
+
+    #[test]
+    fn test_try_keygen_encaps() {
+        let (pk, vault) = try_keygen().unwrap();
+        let (ct, key_bob) = try_encaps(&pk).unwrap();
+        assert_eq!(decaps(&ct, &vault).key, key_bob.key);
+
+        let mut buf = [0u8; 32];
+        assert_eq!(try_fill(&mut FailingRng, &mut buf), Err(MirError::Rng));
+        assert_eq!(try_fill(&mut TestRng::new(13), &mut buf), Ok(()));
+    }
+
+    #[test]
+    fn test_fallible_encaps_rng_failure() {
+        let (pk, _) = keygen_from_seed(&[0x4Au8; 32]).unwrap();
+
+        let mut scratch = [0i16; K * N];
+        let mut ct = MirCiphertext::default();
+        encaps_into(&pk, &mut TestRng::new(24), &mut scratch, &mut ct).unwrap();
+        assert_eq!(encaps_into(&pk, &mut FailingRng, &mut scratch, &mut ct).err(), Some(MirError::Rng));
+        assert_eq!(ct.to_bytes(), MirCiphertext::default().to_bytes());
+
+        let matrix = poly::gen_matrix(&pk.seed);
+        assert!(matches!(encaps_with_matrix(&pk, &matrix, &mut FailingRng), Err(MirError::Rng)));
+
+        let mut key = [0xAAu8; SHARED_LEN];
+        assert_eq!(MirConfig::new().encaps_bytes(&pk, &mut FailingRng, &mut key).err(), Some(MirError::Rng));
+        assert_eq!(key, [0u8; SHARED_LEN]);
+    }
+
+    #[test]
+    fn test_weak_secret_rejection() {
+        let mut s = [0i16; K * N];
+        assert_eq!(secret_weight(&s), 0);
+        s[..3].copy_from_slice(&[1, -1, 0]);
+        assert_eq!(secret_weight(&s), 2);
+
+        let (_, vault) = keygen_with_rng(&mut TestRng::new(14));
+        let weight = vault.access(secret_weight);
+        assert_eq!(weight, vault.access(|s| s.iter().filter(|&&c| c != 0).count()));
+        assert!(weight >= MIN_SECRET_WEIGHT);
+
+        assert!(matches!(keygen_fresh(&mut FailingRng), Err(MirError::Rng)));
+    }
+
+    #[test]
+    fn test_keygen_checked() {
+        let (pk, vault) = keygen_checked(&mut TestRng::new(10)).unwrap();
+        let (ct, key_bob) = encaps(&pk);
+        assert_eq!(decaps(&ct, &vault).key, key_bob.key);
+
+        // The check itself rejects a vault that does not belong to the key
+        let (_, other) = keygen();
+        assert!(pairwise_consistent(&pk, &vault, &mut TestRng::new(11)));
+        assert!(!pairwise_consistent(&pk, &other, &mut TestRng::new(11)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_vault_shared_across_threads() {
+        use std::sync::Arc;
+        use std::vec::Vec;
+
+        let (pk, vault) = keygen();
+        let vault = Arc::new(vault);
+        let exchanges: Vec<_> = (0..8).map(|i| encaps_with_rng(&pk, &mut TestRng::new(30 + i))).collect();
+
+        let handles: Vec<_> = exchanges
+            .into_iter()
+            .map(|(ct, key)| {
+                let vault = Arc::clone(&vault);
+                std::thread::spawn(move || decaps(&ct, &vault).key == key.key)
+            })
+            .collect();
+        for handle in handles {
+            assert!(handle.join().unwrap());
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_pubkey_write_read() {
+        let (pk, _) = keygen();
+        let mut sink = std::vec::Vec::new();
+        pk.write_to(&mut sink).unwrap();
+        assert_eq!(sink[..], pk.to_bytes()[..]);
+
+        let pk2 = MirPubkey::read_from(&mut &sink[..]).unwrap();
+        assert_eq!(pk2.to_bytes(), pk.to_bytes());
+
+        let err = MirPubkey::read_from(&mut &sink[..PUBKEY_BYTES - 1]).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_decaps_from_reader() {
+        let (pk, vault) = keygen();
+        let (ct, key_bob) = encaps(&pk);
+        let mut stream = std::vec::Vec::new();
+        stream.extend_from_slice(&ct.to_bytes());
+        stream.extend_from_slice(b"next message");
+
+        let mut r = &stream[..];
+        assert_eq!(decaps_from_reader(&mut r, &vault).unwrap().key, key_bob.key);
+        assert_eq!(r, b"next message");
+
+        let bytes = ct.to_bytes();
+        let res = decaps_from_reader(&mut &bytes[..CIPHERTEXT_BYTES - 1], &vault);
+        assert_eq!(res.err(), Some(MirError::BadLength));
+
+        // A wrong magic stops after the header
+        let mut bad = bytes;
+        bad[0] ^= 1;
+        let mut r = &bad[..];
+        assert_eq!(decaps_from_reader(&mut r, &vault).err(), Some(MirError::Malformed));
+        assert_eq!(r.len(), CIPHERTEXT_BYTES - HEADER_BYTES);
+
+        let mut bad = bytes;
+        bad[CIPHERTEXT_BYTES - 2] ^= 1;
+        assert_eq!(decaps_from_reader(&mut &bad[..], &vault).err(), Some(MirError::InconsistentCount));
+    }
+
+    #[cfg(feature = "subtle")]
+    #[test]
+    fn test_decaps_ct() {
+        use subtle::{ConditionallySelectable, ConstantTimeEq};
+
+        let (pk, vault) = keygen();
+        let (ct, key_bob) = encaps_with_rng(&pk, &mut TestRng::new(19));
+        let (key, valid) = decaps_ct(&ct, &vault);
+        assert!(bool::from(valid));
+        assert!(bool::from(key.ct_eq(&key_bob)));
+
+        // Implicit rejection: an inconsistent count selects the rejection key
+        let mut bad = ct;
+        bad.cnt ^= 1;
+        let reject = MirSharedKey { key: [0xAA; SHARED_LEN] };
+        let (key, valid) = decaps_ct(&bad, &vault);
+        assert!(!bool::from(valid));
+        assert_eq!(MirSharedKey::conditional_select(&reject, &key, valid).key, reject.key);
+        let (key, valid) = decaps_ct(&ct, &vault);
+        assert_eq!(MirSharedKey::conditional_select(&reject, &key, valid).key, key_bob.key);
+
+        let picked = MirCiphertext::conditional_select(&ct, &bad, subtle::Choice::from(1));
+        assert_eq!(picked.to_bytes(), bad.to_bytes());
+    }
+
+    #[cfg(feature = "mac")]
+    #[test]
+    fn test_encaps_mac() {
+        let (pk, vault) = keygen();
+        let (ct, tag, key_bob) = encaps_mac_with_rng(&pk, &mut TestRng::new(23));
+        let (ct2, tag2) = MirCiphertext::from_bytes_with_mac(&ct.to_bytes_with_mac(&tag)).unwrap();
+        assert_eq!(decaps_mac(&ct2, &tag2, &vault).unwrap().key, key_bob.key);
+
+        // Any change to the ciphertext or the tag is caught
+        let mut bad = ct;
+        bad.u[3] ^= 0x01;
+        assert_eq!(decaps_mac(&bad, &tag, &vault).err(), Some(MirError::InvalidCiphertext));
+        let mut bad_tag = tag;
+        bad_tag[MAC_LEN - 1] ^= 0x80;
+        assert_eq!(decaps_mac(&ct, &bad_tag, &vault).err(), Some(MirError::InvalidCiphertext));
+
+        let bytes = ct.to_bytes_with_mac(&tag);
+        assert_eq!(MirCiphertext::from_bytes_with_mac(&bytes[..CIPHERTEXT_BYTES]).err(), Some(MirError::BadLength));
+    }
+
+    #[test]
+    fn test_encaps_signed() {
+        // Toy keyed hash standing in for a real signature scheme
+        let tag = |bytes: &[u8]| {
+            let mut out = [0u8; 32];
+            mir_sha3_256(&mut out, &[b"signing-key", bytes], 0xFF);
+            out
+        };
+
+        let (pk, vault) = keygen();
+        let mut seen = [0u8; CIPHERTEXT_BYTES];
+        let (ct, sig, key_bob) = encaps_signed(&pk, &mut TestRng::new(20), |bytes| {
+            seen.copy_from_slice(bytes);
+            tag(bytes)
+        });
+        assert_eq!(seen, ct.to_bytes());
+        let verify = |bytes: &[u8], sig: &[u8]| ct::eq_slice(&tag(bytes), sig);
+        assert_eq!(decaps_verified(&ct, &sig, &vault, verify).unwrap().key, key_bob.key);
+
+        let mut forged = ct;
+        forged.u[0] ^= 1;
+        assert!(matches!(decaps_verified(&forged, &sig, &vault, verify), Err(MirError::BadSignature)));
+    }
+
+    #[test]
+    fn test_seed_backed_vault() {
+        let (pk, full) = keygen();
+        let seed = full.export_secret_seed().unwrap();
+        let compact = MirSecretVault::seed_backed(&seed);
+        assert!(compact.is_seed_backed());
+        assert!(!full.is_seed_backed());
+
+        // The re-derived `s` is the resident one
+        full.access(|s_full| compact.access(|s_derived| assert_eq!(s_full, s_derived)));
+
+        let (ct, key_bob) = encaps(&pk);
+        assert_eq!(decaps(&ct, &compact).key, key_bob.key);
+        assert_eq!(compact.export_secret_seed().unwrap().as_bytes(), seed.as_bytes());
+    }
+
+    #[test]
+    fn test_rotate_public_seed() {
+        let mut kp = MirKeypair::from(keygen_with_rng(&mut TestRng::new(14)));
+        let old = kp.public.clone();
+        kp.rotate_public_seed(&mut TestRng::new(15));
+        assert_ne!(kp.public.seed, old.seed);
+        assert_ne!(kp.public.b[..], old.b[..]);
+
+        // Same secret: both the new and the still-circulating old key decapsulate
+        for pk in [&kp.public, &old] {
+            let (ct, key_bob) = encaps(pk);
+            assert_eq!(decaps(&ct, &kp.vault).key, key_bob.key);
+        }
+    }
+
+    #[test]
+    fn test_pubkey_parts_roundtrip() {
+        let (pk, vault) = keygen();
+        let rebuilt = MirPubkey::from_parts(*pk.seed(), *pk.b());
+        assert_eq!(rebuilt.seed, pk.seed);
+        assert_eq!(rebuilt.b[..], pk.b[..]);
+
+        let derived = public_from_vault(pk.seed(), &vault);
+        assert_eq!(derived.b[..], pk.b[..]);
+    }
+
+    #[cfg(feature = "bench")]
+    #[test]
+    fn test_bench_roundtrip() {
+        let (kg, en, de) = bench_roundtrip(2);
+        assert!(kg > core::time::Duration::ZERO);
+        assert!(en > core::time::Duration::ZERO);
+        assert!(de > core::time::Duration::ZERO);
+    }
+
+    #[test]
+    fn test_ciphertext_validity() {
+        let (pk, vault) = keygen();
+        let (mut ct, key_bob) = encaps(&pk);
+        assert!(ct.is_valid());
+        assert_eq!(decaps_checked(&ct, &vault).unwrap().key, key_bob.key);
+
+        ct.cnt ^= 1;
+        assert!(!ct.is_valid());
+        assert_eq!(decaps_checked(&ct, &vault).err(), Some(MirError::InconsistentCount));
+        ct.cnt = N as u16 + 1;
+        assert!(!ct.is_valid());
+        assert_eq!(decaps_checked(&ct, &vault).err(), Some(MirError::OutOfRange));
+
+        ct.canonicalize();
+        assert!(ct.is_valid());
+        assert_eq!(decaps_checked(&ct, &vault).unwrap().key, key_bob.key);
+
+        let mut full = MirCiphertext { mask: [0xFF; N / 8], ..Default::default() };
+        assert!(!full.is_valid());
+        full.canonicalize();
+        assert_eq!(full.cnt as usize, N);
+        assert!(full.is_valid());
+    }
+
+    #[test]
+    fn test_ciphertext_ct_select() {
+        let (pk, _) = keygen();
+        let (a, _) = encaps(&pk);
+        let (b, _) = encaps(&pk);
+        assert_eq!(MirCiphertext::ct_select(&a, &b, 1).to_bytes(), a.to_bytes());
+        assert_eq!(MirCiphertext::ct_select(&a, &b, 0).to_bytes(), b.to_bytes());
+    }
+
+    #[test]
+    fn test_encaps_deterministic() {
+        let (pk, vault) = keygen();
+        let coins = [0x5Au8; 32];
+        let (ct1, key1) = encaps_deterministic(&pk, &coins);
+        let (ct2, key2) = encaps_deterministic(&pk, &coins);
+        assert_eq!(ct1.u[..], ct2.u[..]);
+        assert_eq!(ct1.mask, ct2.mask);
+        assert_eq!(ct1.cnt, ct2.cnt);
+        assert_eq!(key1.key, key2.key);
+        assert_eq!(decaps(&ct1, &vault).key, key1.key);
+
+        let (ct3, _) = encaps_deterministic(&pk, &[0xA5u8; 32]);
+        assert_ne!(ct1.u[..], ct3.u[..]);
+    }
+
+    #[test]
+    fn test_encaps_deterministic_checked() {
+        let (pk, vault) = keygen_from_seed(&[0x21u8; 32]).unwrap();
+        let coins = [0x22u8; 32];
+
+        let (ct, key) = encaps_deterministic_checked(&pk, &coins, 1).unwrap();
+        let (ct2, _) = encaps_deterministic_checked(&pk, &coins, ct.cnt).unwrap();
+        assert_eq!(ct.cnt, ct2.cnt);
+        assert_eq!(decaps(&ct, &vault).key, key.key);
+
+        assert!(matches!(
+            encaps_deterministic_checked(&pk, &coins, ct.cnt + 1),
+            Err(MirError::InsufficientEntropy)
+        ));
+        assert!(matches!(
+            encaps_deterministic_checked(&pk, &coins, N as u16 + 1),
+            Err(MirError::InsufficientEntropy)
+        ));
+    }
+
+    #[test]
+    fn test_encaps_det() {
+        let (pk, vault) = keygen();
+        let (ct1, key1) = encaps_det(&pk, b"file-block-0001");
+        let (ct2, key2) = encaps_det(&pk, b"file-block-0001");
+        assert_eq!(ct1.to_bytes(), ct2.to_bytes());
+        assert_eq!(key1.key, key2.key);
+        assert_eq!(decaps(&ct1, &vault).key, key1.key);
+
+        let (ct3, key3) = encaps_det(&pk, b"file-block-0002");
+        assert_ne!(ct1.u, ct3.u);
+        assert_ne!(key1.key, key3.key);
+
+        // Bound to the recipient key as well as the message
+        let (other, _) = keygen();
+        assert_ne!(encaps_det(&other, b"file-block-0001").1.key, key1.key);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_matrix_cache() {
+        let cache = MatrixCache::new(2);
+        let (pk1, vault1) = keygen();
+        let (pk2, _) = keygen();
+        let (pk3, _) = keygen();
+        assert!(cache.is_empty());
+
+        let a1 = cache.get_or_expand(&pk1);
+        assert_eq!(*a1, poly::gen_matrix(&pk1.seed));
+        assert!(std::sync::Arc::ptr_eq(&a1, &cache.get_or_expand(&pk1)));
+
+        // pk1 was used more recently than pk2, so pk2 is evicted for pk3
+        cache.get_or_expand(&pk2);
+        cache.get_or_expand(&pk1);
+        cache.get_or_expand(&pk3);
+        assert_eq!(cache.len(), 2);
+        assert!(std::sync::Arc::ptr_eq(&a1, &cache.get_or_expand(&pk1)));
+
+        let (ct, key_bob) = encaps_with_matrix(&pk1, &a1, &mut TestRng::new(9)).unwrap();
+        assert_eq!(decaps(&ct, &vault1).key, key_bob.key);
+        assert!(matches!(
+            encaps_with_matrix(&pk1, &a1[1..], &mut TestRng::new(9)),
+            Err(MirError::BadLength)
+        ));
+    }
+
+    #[test]
+    fn test_encaps_expanded_matches_encaps() {
+        let (pk, vault) = keygen();
+        let expanded = MirPubkeyExpanded::new(pk.clone());
+        assert_eq!(expanded.matrix(), &poly::gen_matrix(&pk.seed)[..]);
+        assert!(expanded.public_key().ct_eq(&pk));
+
+        // Same coins, same output as the plain path
+        let (ct, key) = encaps_expanded_with_rng(&expanded, &mut TestRng::new(40));
+        let (ct2, key2) = encaps_with_rng(&pk, &mut TestRng::new(40));
+        assert_eq!(ct.to_bytes()[..], ct2.to_bytes()[..]);
+        assert_eq!(key.key, key2.key);
+        assert_eq!(decaps(&ct, &vault).key, key.key);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_encaps_expanded_shared_across_threads() {
+        use std::sync::Arc;
+
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<MirPubkeyExpanded>();
+        assert_send_sync::<Arc<MirPubkeyExpanded>>();
+
+        let (pk, vault) = keygen();
+        let cache = MatrixCache::new(1);
+        let expanded = Arc::new(MirPubkeyExpanded::from_cache(pk.clone(), &cache));
+        assert!(Arc::ptr_eq(&expanded.matrix, &cache.get_or_expand(&pk)));
+
+        let vault = Arc::new(vault);
+        let workers: std::vec::Vec<_> = (0..4)
+            .map(|_| {
+                let (expanded, vault) = (Arc::clone(&expanded), Arc::clone(&vault));
+                std::thread::spawn(move || {
+                    let (ct, key) = encaps_expanded(&expanded);
+                    decaps(&ct, &vault).key == key.key
+                })
+            })
+            .collect();
+        for worker in workers {
+            assert!(worker.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_encaps_into_reuses_buffers() {
+        let (pk, vault) = keygen();
+        let mut rng = TestRng::new(3);
+        let mut scratch = [0i16; K * N];
+        let mut ct = MirCiphertext::default();
+
+        for _ in 0..3 {
+            let key_bob = encaps_into(&pk, &mut rng, &mut scratch, &mut ct).unwrap();
+            assert_eq!(decaps(&ct, &vault).key, key_bob.key);
+            assert!(scratch.iter().all(|&c| c == 0));
+        }
+
+        let mut short = [0i16; K * N - 1];
+        assert_eq!(
+            encaps_into(&pk, &mut rng, &mut short, &mut ct).err(),
+            Some(MirError::BadLength)
+        );
+        // A failed call leaves the default ciphertext, not the previous one
+        assert_eq!(ct.to_bytes(), MirCiphertext::default().to_bytes());
+    }
+
+    #[cfg(feature = "aes-expand")]
+    #[test]
+    fn test_aes_expand_keypair() {
+        assert!(self_test());
+
+        // The AES stream must not coincide with the SHAKE stream for the same seed.
+        let seed = [9u8; SEED_LEN];
+        let mut aes_block = [0i16; N];
+        poly::expand_matrix(&seed, &mut aes_block);
+        let mut shake = [0u8; 2 * N];
+        mir_shake_reader(&seed, domains::MATRIX).read(&mut shake);
+        let shake_block: [i16; N] = core::array::from_fn(|j| {
+            (u16::from_le_bytes([shake[2 * j], shake[2 * j + 1]]) as i32 & Q_MASK) as i16
+        });
+        assert_ne!(aes_block, shake_block);
+        assert!(aes_block.iter().all(|&c| (0..Q as i16).contains(&c)));
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_recon_stats() {
+        let (pk, _) = keygen_from_seed(&[0x4Au8; 32]).unwrap();
+        let cts: [MirCiphertext; 3] = core::array::from_fn(|i| encaps_with_rng(&pk, &mut TestRng::new(40 + i as u8)).0);
+
+        let mut stats = ReconStats::new();
+        assert_eq!(stats.summary().exchanges, 0);
+        assert_eq!(stats.summary().cnt_mean, 0.0);
+        cts.iter().for_each(|ct| stats.observe(ct));
+
+        let sum = stats.summary();
+        assert_eq!(sum.exchanges, 3);
+        assert_eq!(sum.cnt_min, cts.iter().map(|ct| ct.cnt).min().unwrap());
+        assert_eq!(sum.cnt_max, cts.iter().map(|ct| ct.cnt).max().unwrap());
+        let total: u32 = cts.iter().map(|ct| ct.cnt as u32).sum();
+        assert_eq!(sum.cnt_mean, total as f64 / 3.0);
+        assert_eq!(sum.cnt_histogram.iter().sum::<u32>(), 3);
+        assert_eq!(sum.selection.iter().sum::<u32>(), total);
+        for (j, &hits) in sum.selection.iter().enumerate() {
+            let want = cts.iter().filter(|ct| ct::bit_get(&ct.mask, j) == 1).count();
+            assert_eq!(hits as usize, want);
+        }
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_flip_bit() {
+        let (pk, vault) = keygen();
+        let (ct, key) = encaps(&pk);
+
+        let mut bad = ct;
+        bad.flip_bit(Region::Mask, 17);
+        assert!(matches!(decaps_checked(&bad, &vault), Err(MirError::InconsistentCount)));
+        bad.flip_bit(Region::Mask, 17);
+        assert_eq!(bad.to_bytes(), ct.to_bytes());
+
+        // A flipped `u` bit is structurally invisible. Bit 6 at a selected
+        // position with s != 0 moves v' by +-64, flipping that key bit.
+        let idx = vault.access(|s| (0..N).find(|&i| ct::bit_get(&ct.mask, i) == 1 && s[i] != 0)).unwrap();
+        bad.flip_bit(Region::U, 8 * idx + 6);
+        assert!(bad.is_valid());
+        assert_eq!(bad.u[idx], ct.u[idx] ^ 0x40);
+        assert_ne!(decaps_checked(&bad, &vault).unwrap().key, key.key);
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_vault_matches_seed() {
+        let seed = [0x13u8; 32];
+        let mut s = zeroed_secret();
+        gen_secret_from_seed(&seed, &mut s);
+        let vault = MirSecretVault::new(s);
+
+        assert!(vault.matches_seed(&seed));
+        assert!(!vault.matches_seed(&[0x14u8; 32]));
+        assert!(!vault.matches_seed(&seed[..MIN_SEED_LEN - 1]));
+    }
+
+    #[test]
+    fn test_export_secret_seed_roundtrip() {
+        let (pk, vault) = keygen();
+        let backup = vault.export_secret_seed().unwrap();
+        let restored = MirSecretVault::from_secret_seed(&backup);
+
+        let (ct, key_bob) = encaps(&pk);
+        assert_eq!(decaps(&ct, &restored).key, key_bob.key);
+        assert_eq!(restored.export_secret_seed().unwrap().as_bytes(), backup.as_bytes());
+
+        assert!(MirSecretVault::new(zeroed_secret()).export_secret_seed().is_none());
+    }
+
+    // Rebuilds the default (variable-length) KDF layout
+    #[cfg(not(feature = "padded-kdf"))]
+    #[test]
+    fn test_recon_bit_shared_by_both_sides() {
+        let (pk, vault) = keygen_from_seed(&[0x4Bu8; 32]).unwrap();
+        let (ct, key_bob) = encaps_deterministic(&pk, &[0x4Cu8; 32]);
+
+        // Rebuild the KDF by hand from the decapsulating side's v' = u * s
+        let mut v_prime = [0u8; N];
+        vault.access(|s| poly::inner_product(&ct.u, s, &mut v_prime));
+        let derive = |bit: u32| {
+            let mut hasher = Shake256::default();
+            hasher.update(&[domains::HASH]);
+            hasher.update(&(SHARED_LEN as u64).to_le_bytes());
+            hasher.update(&ct.cnt.to_le_bytes());
+            for (idx, &v) in v_prime.iter().enumerate() {
+                if ct::bit_get(&ct.mask, idx) == 1 {
+                    hasher.update(&[(v >> bit) & 1]);
+                }
+            }
+            let mut key = [0u8; SHARED_LEN];
+            hasher.finalize_xof().read(&mut key);
+            key
+        };
+
+        // The encapsulator's key is exactly the RECON_BIT extraction of v'
+        assert_eq!(derive(RECON_BIT), key_bob.key);
+        assert_ne!(derive(RECON_BIT - 1), key_bob.key);
+        assert_ne!(derive(RECON_BIT + 1), key_bob.key);
+    }
+
+    #[test]
+    fn test_coefficient_histogram() {
+        let (pk, _) = keygen_from_seed(&[0x52u8; 32]).unwrap();
+        let hist = pk.coefficient_histogram();
+        assert_eq!(hist.iter().sum::<u32>(), (K * N) as u32);
+        // Expected 5 per value; a fair key never comes near a tenth of b
+        assert!(*hist.iter().max().unwrap() < (K * N / 10) as u32);
+
+        let degenerate = MirPubkey::from_parts([0u8; SEED_LEN], [0x42; K * N]);
+        let hist = degenerate.coefficient_histogram();
+        assert_eq!(hist[0x42], (K * N) as u32);
+        assert_eq!(hist.iter().filter(|&&c| c > 0).count(), 1);
+    }
+
+    #[test]
+    fn test_shared_key_ratchet() {
+        let (pk, vault) = keygen();
+        let (ct, mut key_bob) = encaps(&pk);
+        let mut key_alice = decaps(&ct, &vault);
+        let start = key_bob.key;
+
+        key_alice.ratchet(b"epoch 1");
+        key_bob.ratchet(b"epoch 1");
+        assert_eq!(key_alice.key, key_bob.key);
+        assert_ne!(key_alice.key, start);
+
+        // Diverging info desynchronizes the two sides
+        key_alice.ratchet(b"epoch 2");
+        key_bob.ratchet(b"epoch 2'");
+        assert_ne!(key_alice.key, key_bob.key);
+    }
+
+    #[test]
+    fn test_vault_from_coefficients() {
+        let (pk, vault) = keygen();
+        let mut s = zeroed_secret();
+        vault.access(|src| s.copy_from_slice(src));
+        let imported = MirSecretVault::from_coefficients(s.clone()).unwrap();
+        let (ct, key_bob) = encaps(&pk);
+        assert_eq!(decaps(&ct, &imported).key, key_bob.key);
+
+        for bad in [2i16, -2, i16::MIN, i16::MAX] {
+            let mut t = s.clone();
+            t[K * N - 1] = bad;
+            assert_eq!(MirSecretVault::from_coefficients(t).err(), Some(MirError::OutOfRange));
+        }
+        s.truncate(K * N - 1);
+        assert_eq!(MirSecretVault::from_coefficients(s).err(), Some(MirError::BadLength));
+    }
+
+    #[test]
+    fn test_decaps_from_bytes() {
+        let (pk, vault) = keygen_from_seed(&[0x48u8; 32]).unwrap();
+        let (ct, key_bob) = encaps_deterministic(&pk, &[0x49u8; 32]);
+        let pk_bytes = pk.to_bytes();
+        let mut ct_bytes = ct.to_bytes();
+        let sk = vault.export_secret_seed().unwrap();
+        assert_eq!(decaps_from_bytes(&pk_bytes, &ct_bytes, sk.as_bytes()).unwrap().key, key_bob.key);
+
+        // A secret from another key pair is caught before decapsulation
+        let (_, other) = keygen_from_seed(&[0x4Au8; 32]).unwrap();
+        let other = other.export_secret_seed().unwrap();
+        let res = decaps_from_bytes(&pk_bytes, &ct_bytes, other.as_bytes());
+        assert_eq!(res.err(), Some(MirError::InconsistentKeypair));
+
+        assert_eq!(decaps_from_bytes(&pk_bytes, &ct_bytes, &[0u8; 31]).err(), Some(MirError::BadLength));
+        assert_eq!(decaps_from_bytes(&ct_bytes, &ct_bytes, sk.as_bytes()).err(), Some(MirError::BadLength));
+        ct_bytes[CIPHERTEXT_BYTES - 2] ^= 0x01;
+        let res = decaps_from_bytes(&pk_bytes, &ct_bytes, sk.as_bytes());
+        assert_eq!(res.err(), Some(MirError::InconsistentCount));
+        ct_bytes[CIPHERTEXT_BYTES - 1] ^= 0x80;
+        let res = decaps_from_bytes(&pk_bytes, &ct_bytes, sk.as_bytes());
+        assert_eq!(res.err(), Some(MirError::OutOfRange));
+    }
+
+    #[test]
+    fn test_pubkey_ct_eq() {
+        let (pk, _) = keygen_from_seed(&[0x47u8; 32]).unwrap();
+        assert!(pk.ct_eq(&pk.clone()));
+
+        let mut other = pk.clone();
+        other.b[K * N - 1] ^= 1;
+        assert!(!pk.ct_eq(&other));
+        let mut other = pk.clone();
+        other.seed[0] ^= 1;
+        assert!(!pk.ct_eq(&other));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_pubkey_display() {
+        use alloc::string::ToString;
+        let (pk, _) = keygen();
+        let shown = pk.to_string();
+        let fp = pk.fingerprint();
+        assert_eq!(shown.len(), "Mirletis/".len() + 16);
+        assert!(shown.starts_with("Mirletis/"));
+        assert_eq!(&shown[9..11], alloc::format!("{:02x}", fp[0]));
+        assert_ne!(fp, pk.fingerprint_with_metadata(b""));
+    }
+
+    #[test]
+    fn test_fingerprint_with_metadata() {
+        let (pk, _) = keygen();
+        let fp = pk.fingerprint_with_metadata(b"kid=1;exp=2027");
+        assert_eq!(fp, pk.clone().fingerprint_with_metadata(b"kid=1;exp=2027"));
+        assert_ne!(fp, pk.fingerprint_with_metadata(b"kid=2;exp=2027"));
+        assert_ne!(fp, pk.fingerprint_with_metadata(b""));
+
+        let (other, _) = keygen();
+        assert_ne!(fp, other.fingerprint_with_metadata(b"kid=1;exp=2027"));
+    }
+
+    #[test]
+    fn test_transcript() {
+        let (pk, vault) = keygen();
+        let (ct, key_bob) = encaps(&pk);
+        let key_alice = decaps(&ct, &vault);
+
+        let mut bob = MirTranscript::new();
+        bob.absorb(&ct.to_bytes());
+        bob.mix_shared(&key_bob);
+        let mut alice = MirTranscript::new();
+        alice.absorb(&ct.to_bytes());
+        alice.mix_shared(&key_alice);
+
+        let (mut out_bob, mut out_alice) = ([0u8; 64], [0u8; 64]);
+        bob.clone().finalize_into(&mut out_bob);
+        alice.finalize_into(&mut out_alice);
+        assert_eq!(out_bob, out_alice);
+
+        // More material extends the transcript
+        bob.absorb(b"finished");
+        let mut later = [0u8; 64];
+        bob.finalize_into(&mut later);
+        assert_ne!(later, out_bob);
+
+        // Item boundaries and item kinds are both committed
+        let squeeze = |items: &[&[u8]], shared: bool| {
+            let mut t = MirTranscript::new();
+            for item in items {
+                if shared {
+                    t.mix_shared(&MirSharedKey { key: (*item).try_into().unwrap() });
+                } else {
+                    t.absorb(item);
+                }
+            }
+            let mut out = [0u8; 32];
+            t.finalize_into(&mut out);
+            out
+        };
+        assert_ne!(squeeze(&[b"ab", b"c"], false), squeeze(&[b"a", b"bc"], false));
+        assert_ne!(squeeze(&[&[7u8; 32]], false), squeeze(&[&[7u8; 32]], true));
+    }
+
+    #[test]
+    fn test_poly_products_match_reference() {
+        let a = poly::gen_matrix(&[0x33u8; SEED_LEN]);
+        let mut x = [0i16; K * N];
+        gen_secret_from_seed(&[0x44u8; 32], &mut x);
+        let mut xb = [0u8; K * N];
+        mir_shake_reader(&[0x55], 0xFF).read(&mut xb);
+
+        // Straightforward definitions over the documented layout
+        let at = |i: usize, l: usize, j: usize| a[(i * K + l) * N + j] as i64;
+        let mut want = ([0u8; K * N], [0u8; K * N], [0u8; N]);
+        for i in 0..K {
+            for j in 0..N {
+                let fwd: i64 = (0..K).map(|l| at(i, l, j) * x[l * N + j] as i64).sum();
+                let tr: i64 = (0..K).map(|l| at(l, i, j) * x[l * N + j] as i64).sum();
+                want.0[i * N + j] = (fwd.rem_euclid(Q as i64) >> SHIFT) as u8;
+                want.1[i * N + j] = (tr.rem_euclid(Q as i64) >> SHIFT) as u8;
+            }
+        }
+        for j in 0..N {
+            let dot: i64 = (0..K).map(|l| xb[l * N + j] as i64 * x[l * N + j] as i64).sum();
+            want.2[j] = dot.rem_euclid(256) as u8;
+        }
+
+        let mut got = ([0u8; K * N], [0u8; K * N], [0u8; N]);
+        poly::matvec(&a, &x, &mut got.0);
+        poly::matvec_transpose(&a, &x, &mut got.1);
+        poly::inner_product(&xb, &x, &mut got.2);
+        assert_eq!(got.0[..], want.0[..]);
+        assert_eq!(got.1[..], want.1[..]);
+        assert_eq!(got.2[..], want.2[..]);
+    }
+
+    #[cfg(feature = "debug-bounds")]
+    #[test]
+    fn test_debug_bounds_reports_shape_bugs() {
+        let a = poly::gen_matrix(&[0x53u8; SEED_LEN]);
+        let s = [0i16; K * N];
+
+        // An output one block short, and a matrix one block short
+        let mut short = [0u8; (K - 1) * N];
+        assert_eq!(poly::try_matvec(&a, &s, &mut short), Err(MirError::BadLength));
+        let mut out = [0u8; K * N];
+        assert_eq!(poly::try_matvec_transpose(&a[..(K * K - 1) * N], &s, &mut out), Err(MirError::BadLength));
+        let mut v = [0u8; N];
+        assert_eq!(poly::try_inner_product(&out[..N], &s, &mut v), Err(MirError::BadLength));
+
+        // `new` accepts an oversized secret; decapsulation reports it
+        #[cfg(feature = "alloc")]
+        {
+            let (pk, _) = keygen();
+            let (ct, _) = encaps(&pk);
+            let vault = MirSecretVault::new(vec![0i16; (K + 1) * N]);
+            assert_eq!(decaps_checked(&ct, &vault).err(), Some(MirError::BadLength));
+        }
+    }
+
+    #[test]
+    fn test_pluggable_matrix_xof() {
+        use poly::{MatrixXof, XofReader};
+
+        // Counts up from the first seed byte; deterministic, not secure
+        struct CounterXof;
+        struct CounterReader(u16);
+        impl XofReader for CounterReader {
+            fn read(&mut self, buf: &mut [u8]) {
+                for pair in buf.chunks_exact_mut(2) {
+                    pair.copy_from_slice(&self.0.to_le_bytes());
+                    self.0 = self.0.wrapping_add(1) & 0x1FFF;
+                }
+            }
+        }
+        impl MatrixXof for CounterXof {
+            type Reader = CounterReader;
+            fn reader(seed: &[u8; SEED_LEN]) -> CounterReader {
+                CounterReader(seed[0] as u16)
+            }
+        }
+
+        let seed = [0x07u8; SEED_LEN];
+        assert_eq!(poly::gen_matrix_with::<poly::DefaultXof>(&seed)[..], poly::gen_matrix(&seed)[..]);
+        let a = poly::gen_matrix_with::<CounterXof>(&seed);
+        assert_eq!(a[..3], [7, 8, 9]);
+        assert_ne!(a[..], poly::gen_matrix(&seed)[..]);
+
+        let mut raw = [0u8; 64];
+        poly::Shake256Xof::expand(&seed, &mut raw);
+        let mut want = [0u8; 64];
+        mir_shake_reader(&seed, domains::MATRIX).read(&mut want);
+        assert_eq!(raw, want);
+
+        // A key pair built on the custom matrix still exchanges keys
+        let (_, vault) = keygen();
+        let mut pk = MirPubkey { seed, b: [0u8; K * N] };
+        vault.access(|s| poly::matvec(&a, s, &mut pk.b));
+        let (ct, key_bob) = encaps_with_matrix(&pk, &a, &mut TestRng::new(29)).unwrap();
+        assert_eq!(decaps(&ct, &vault).key, key_bob.key);
+    }
+
+    #[test]
+    fn test_matrix_rejections_are_rare() {
+        // Expected rejections per matrix: K * K * N * (2^13 - q) / 2^13
+        let mut total = 0;
+        for seed in 0u8..8 {
+            let mut stream = poly::MatrixStream::new(&[seed; SEED_LEN]);
+            let mut a = [0i16; K * K * N];
+            stream.fill(&mut a);
+            assert!(a.iter().all(|&c| (c as i32) < Q));
+            assert_eq!(a[..], poly::gen_matrix(&[seed; SEED_LEN])[..]);
+            total += stream.rejected();
+        }
+        #[cfg(not(feature = "prime-modulus"))]
+        assert_eq!(total, 0);
+        #[cfg(feature = "prime-modulus")]
+        assert!(total <= 32, "{} rejections over 8 matrices", total);
+    }
+
+    #[test]
+    fn test_matvec_transpose_is_transpose() {
+        let a = poly::gen_matrix(&[0x34u8; SEED_LEN]);
+
+        // Explicit transpose: block (i, l) of A^T is block (l, i) of A
+        let mut a_t = [0i16; K * K * N];
+        for i in 0..K {
+            for l in 0..K {
+                let dst = (i * K + l) * N;
+                let src = (l * K + i) * N;
+                a_t[dst..dst + N].copy_from_slice(&a[src..src + N]);
+            }
+        }
+
+        // keygen's A * s on the transposed matrix must equal encaps' A^T * r
+        let mut r = [0i16; K * N];
+        gen_secret_from_seed(&[0x45u8; 32], &mut r);
+        let mut want = [0u8; K * N];
+        let mut got = [0u8; K * N];
+        poly::matvec(&a_t, &r, &mut want);
+        poly::matvec_transpose(&a, &r, &mut got);
+        assert_eq!(got[..], want[..]);
+    }
+
+    #[test]
+    fn test_products_exact_at_max_rank() {
+        // Maximal coefficients at the largest admissible rank, checked in i64
+        const KMAX: usize = 10;
+        let a = [(Q - 1) as i16; KMAX * KMAX * N];
+        let xb = [u8::MAX; KMAX * N];
+        for &coeff in [1i16, -1].iter() {
+            let y = [coeff; KMAX * N];
+            let mut got = [0u8; KMAX * N];
+
+            let acc = KMAX as i64 * (Q - 1) as i64 * coeff as i64;
+            let want = (acc.rem_euclid(Q as i64) >> SHIFT) as u8;
+            poly::matvec(&a, &y, &mut got);
+            assert!(got.iter().all(|&c| c == want));
+            poly::matvec_transpose(&a, &y, &mut got);
+            assert!(got.iter().all(|&c| c == want));
+
+            let dot = KMAX as i64 * u8::MAX as i64 * coeff as i64;
+            poly::inner_product(&xb, &y, &mut got[..N]);
+            assert!(got[..N].iter().all(|&c| c == dot.rem_euclid(256) as u8));
+        }
+    }
+
+    #[test]
+    fn test_extreme_secrets_roundtrip() {
+        for &coeff in [1i16, -1].iter() {
+            let mut s = zeroed_secret();
+            s.iter_mut().for_each(|c| *c = coeff);
+            let vault = MirSecretVault::new(s);
+            let pk = public_from_vault(&[0x66u8; SEED_LEN], &vault);
+
+            // |acc| reaches K * (q - 1); the reduction must still be exact
+            let a = poly::gen_matrix(&pk.seed);
+            for i in 0..K {
+                for j in 0..N {
+                    let acc: i64 = (0..K).map(|l| a[(i * K + l) * N + j] as i64 * coeff as i64).sum();
+                    assert_eq!(pk.b[i * N + j], (acc.rem_euclid(Q as i64) >> SHIFT) as u8);
+                }
+            }
+
+            let mut rng = TestRng::new(16);
+            for _ in 0..32 {
+                let (ct, key_bob) = encaps_with_rng(&pk, &mut rng);
+                assert!(ct.cnt > 0);
+                assert_eq!(decaps(&ct, &vault).key, key_bob.key, "s = [{}; K * N]", coeff);
+            }
+        }
+    }
+
+    #[test]
+    fn test_poly_reproduces_public_key() {
+        let (pk, vault) = keygen();
+        let matrix_a = poly::gen_matrix(&pk.seed);
+        let mut b = [0u8; K * N];
+        vault.access(|s| poly::matvec(&matrix_a, s, &mut b));
+        assert_eq!(b[..], pk.b[..]);
+    }
 }
 
+
+/// Wire-format compatibility: blobs serialized by an earlier build must keep
+/// parsing to the same values and decapsulating to the same key.
+#[cfg(test)]
+mod compat_tests {
+    use super::*;
+
+    fn unhex<const LEN: usize>(s: &str) -> [u8; LEN] {
+        let s = s.trim();
+        assert_eq!(s.len(), 2 * LEN, "hex blob has the wrong length");
+        let mut out = [0u8; LEN];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).unwrap();
+        }
+        out
+    }
+
+    #[cfg_attr(any(feature = "aes-expand", feature = "prime-modulus", feature = "padded-kdf"), allow(dead_code))]
+    mod v1 {
+        pub const SEED: &str = include_str!("../testdata/v1/seed.hex");
+        pub const COINS: &str = include_str!("../testdata/v1/coins.hex");
+        pub const PUBKEY: &str = include_str!("../testdata/v1/pubkey.hex");
+        pub const CIPHERTEXT: &str = include_str!("../testdata/v1/ciphertext.hex");
+        pub const SHARED: &str = include_str!("../testdata/v1/shared.hex");
+    }
+
+    // Builds that are not wire-compatible refuse the blobs by version.
+    #[cfg(any(feature = "aes-expand", feature = "prime-modulus", feature = "padded-kdf"))]
+    #[test]
+    fn test_v1_blobs_rejected_by_variant() {
+        let pk_bytes: [u8; PUBKEY_BYTES] = unhex(v1::PUBKEY);
+        let ct_bytes: [u8; CIPHERTEXT_BYTES] = unhex(v1::CIPHERTEXT);
+        assert_eq!(MirPubkey::from_bytes(&pk_bytes).err(), Some(MirError::UnsupportedVersion));
+        assert_eq!(MirCiphertext::from_bytes(&ct_bytes).err(), Some(MirError::UnsupportedVersion));
+    }
+
+    #[cfg(not(any(feature = "aes-expand", feature = "prime-modulus", feature = "padded-kdf")))]
+    #[test]
+    fn test_v1_blobs_parse() {
+        let pk_bytes: [u8; PUBKEY_BYTES] = unhex(v1::PUBKEY);
+        let ct_bytes: [u8; CIPHERTEXT_BYTES] = unhex(v1::CIPHERTEXT);
+
+        let pk = MirPubkey::from_bytes(&pk_bytes).unwrap();
+        assert_eq!(pk.to_bytes()[..], pk_bytes[..]);
+
+        let ct = MirCiphertext::from_bytes(&ct_bytes).unwrap();
+        assert_eq!(ct.to_bytes()[..], ct_bytes[..]);
+        assert_eq!(ct.cnt, 85);
+        let popcount: u32 = ct.mask.iter().map(|b| b.count_ones()).sum();
+        assert_eq!(popcount, ct.cnt as u32);
+    }
+
+    // The blobs were produced with the default SHAKE-256 matrix expansion.
+    #[cfg(not(any(feature = "aes-expand", feature = "prime-modulus", feature = "padded-kdf")))]
+    #[test]
+    fn test_v1_blobs_decapsulate() {
+        let seed: [u8; 32] = unhex(v1::SEED);
+        let coins: [u8; 32] = unhex(v1::COINS);
+        let shared: [u8; SHARED_LEN] = unhex(v1::SHARED);
+        let pk_bytes: [u8; PUBKEY_BYTES] = unhex(v1::PUBKEY);
+        let ct_bytes: [u8; CIPHERTEXT_BYTES] = unhex(v1::CIPHERTEXT);
+
+        let (pk, vault) = keygen_from_seed(&seed).unwrap();
+        assert_eq!(pk.to_bytes()[..], pk_bytes[..], "keygen_from_seed output changed");
+
+        let ct = MirCiphertext::from_bytes(&ct_bytes).unwrap();
+        assert_eq!(decaps(&ct, &vault).key, shared, "decaps output changed");
+
+        let (ct2, key2) = encaps_deterministic(&pk, &coins);
+        assert_eq!(ct2.to_bytes()[..], ct_bytes[..], "encaps_deterministic output changed");
+        assert_eq!(key2.key, shared);
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_bad_length() {
+        let pk = MirPubkey::default().to_bytes();
+        assert_eq!(MirPubkey::from_bytes(&pk[1..]).err(), Some(MirError::BadLength));
+        let ct = MirCiphertext::default().to_bytes();
+        assert_eq!(MirCiphertext::from_bytes(&ct[..ct.len() - 1]).err(), Some(MirError::BadLength));
+    }
+
+    #[test]
+    fn test_pubkey_compressed_roundtrip() {
+        let (pk, _) = keygen_from_seed(&[0x4Fu8; 32]).unwrap();
+        let bytes = pk.to_bytes_compressed();
+        assert_eq!(&bytes[..HEADER_BYTES], &pk.to_bytes()[..HEADER_BYTES]);
+        assert!(MirPubkey::from_bytes_compressed(&bytes).unwrap().ct_eq(&pk));
+
+        // Every producible coefficient value, in every position
+        let mut all = MirPubkey::default();
+        for (i, v) in all.b.iter_mut().enumerate() {
+            *v = (i % (1 << B_BITS)) as u8;
+        }
+        let back = MirPubkey::from_bytes_compressed(&all.to_bytes_compressed()).unwrap();
+        assert_eq!(back.b, all.b);
+
+        assert_eq!(MirPubkey::from_bytes_compressed(&bytes[1..]).err(), Some(MirError::BadLength));
+    }
+
+    #[test]
+    fn test_coeff_packing_partial_last_byte() {
+        // Odd counts at narrow widths leave a partly filled final byte
+        for bits in 1..=8u32 {
+            for len in [1usize, 3, 7, 13] {
+                let src: [u8; 13] = core::array::from_fn(|i| ((i * 37 + 5) as u32 & ((1 << bits) - 1)) as u8);
+                let src = &src[..len];
+                let mut packed = [0u8; 13];
+                let packed = &mut packed[..(len * bits as usize).div_ceil(8)];
+                pack_coeffs(src, bits, packed);
+
+                let mut back = [0u8; 13];
+                unpack_coeffs(packed, bits, &mut back[..len]).unwrap();
+                assert_eq!(&back[..len], src, "bits = {}, len = {}", bits, len);
+
+                // Stray padding is not canonical
+                if !(len * bits as usize).is_multiple_of(8) {
+                    *packed.last_mut().unwrap() |= 0x80;
+                    let res = unpack_coeffs(packed, bits, &mut back[..len]);
+                    assert_eq!(res, Err(MirError::Malformed));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_ciphertext_errors_by_malformation() {
+        let (pk, _) = keygen_from_seed(&[0x50u8; 32]).unwrap();
+        let (ct, _) = encaps_deterministic(&pk, &[0x51u8; 32]);
+        let bytes = ct.to_bytes();
+        let with = |at: usize, v: u8| {
+            let mut b = bytes;
+            b[at] = v;
+            MirCiphertext::from_bytes(&b)
+        };
+        let cnt_at = CIPHERTEXT_BYTES - 2;
+
+        assert_eq!(MirCiphertext::from_bytes(&bytes[..10]).err(), Some(MirError::BadLength));
+        assert_eq!(with(0, b'X').err(), Some(MirError::Malformed));
+        assert_eq!(with(MAGIC.len(), FORMAT_VERSION ^ 0x3F).err(), Some(MirError::UnsupportedVersion));
+        assert_eq!(with(cnt_at + 1, 0x01).err(), Some(MirError::OutOfRange));
+        assert_eq!(MirCiphertext::peek_cnt(&with_cnt(bytes, N as u16 + 1)).err(), Some(MirError::OutOfRange));
+        assert_eq!(MirCiphertext::peek_cnt(&with_cnt(bytes, N as u16)), Ok(N as u16));
+
+        // A count in range but not matching the mask parses, and fails `check`
+        let ct = MirCiphertext::from_bytes(&with_cnt(bytes, ct.cnt ^ 1)).unwrap();
+        assert_eq!(ct.check().err(), Some(MirError::InconsistentCount));
+    }
+
+    fn with_cnt(mut bytes: [u8; CIPHERTEXT_BYTES], cnt: u16) -> [u8; CIPHERTEXT_BYTES] {
+        bytes[CIPHERTEXT_BYTES - 2..].copy_from_slice(&cnt.to_le_bytes());
+        bytes
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_error_display() {
+        use alloc::string::ToString;
+        let all = [
+            MirError::BadLength,
+            MirError::Malformed,
+            MirError::OutOfRange,
+            MirError::InconsistentCount,
+            MirError::InsufficientEntropy,
+            MirError::InconsistentKeypair,
+            MirError::Rng,
+            MirError::BadChecksum,
+            MirError::BadSignature,
+            MirError::UnsupportedVersion,
+            MirError::InvalidCiphertext,
+            MirError::Io,
+        ];
+        for (i, a) in all.iter().enumerate() {
+            assert!(!a.to_string().is_empty());
+            for b in &all[i + 1..] {
+                assert_ne!(a.to_string(), b.to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn test_peek_cnt() {
+        let (pk, _) = keygen_from_seed(&[0x4Du8; 32]).unwrap();
+        let (ct, _) = encaps_deterministic(&pk, &[0x4Eu8; 32]);
+        let bytes = ct.to_bytes();
+        assert_eq!(MirCiphertext::peek_cnt(&bytes), Ok(ct.cnt));
+        assert_eq!(MirCiphertext::peek_cnt(&bytes[..bytes.len() - 1]).err(), Some(MirError::BadLength));
+        assert_eq!(MirCiphertext::peek_cnt(&[]).err(), Some(MirError::BadLength));
+
+        let mut bad = bytes;
+        bad[0] ^= 1;
+        assert_eq!(MirCiphertext::peek_cnt(&bad).err(), Some(MirError::Malformed));
+    }
+
+    #[test]
+    fn test_from_bytes_checks_header() {
+        let mut pk = MirPubkey::default().to_bytes();
+        let mut ct = MirCiphertext::default().to_bytes();
+        assert_eq!(&pk[..MAGIC.len()], b"MIRL");
+        assert_eq!(&ct[..HEADER_BYTES], &pk[..HEADER_BYTES]);
+        assert_eq!(pk[MAGIC.len()], FORMAT_VERSION);
+        #[cfg(not(any(feature = "aes-expand", feature = "prime-modulus", feature = "padded-kdf")))]
+        assert_eq!(FORMAT_VERSION, 1);
+
+        pk[MAGIC.len()] ^= 0x02;
+        assert_eq!(MirPubkey::from_bytes(&pk).err(), Some(MirError::UnsupportedVersion));
+        ct[0] = b'X';
+        assert_eq!(MirCiphertext::from_bytes(&ct).err(), Some(MirError::Malformed));
+    }
+}
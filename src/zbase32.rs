@@ -0,0 +1,252 @@
+/*
+ * z-base-32 text encoding for MirPubkey / MirCiphertext (out-of-band transfer).
+ *
+ * Layout: zbase32( to_bytes() || check ), where `check` is the first byte of
+ * SHA3-256 over the serialized bytes (own domain tag). Bits are packed
+ * MSB-first, 5 per character, with no padding; unused trailing bits are zero.
+ *
+ * The alphabet avoids look-alike characters, so a code read aloud or typed by
+ * hand survives. The decoder also accepts upper case, and rejects anything
+ * else that is not canonical.
+ *
+ * The codec works on fixed-size buffers, so `write_zbase32` / `from_zbase32`
+ * need no allocator; `to_zbase32` is the `String` convenience on top.
+ */
+
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+
+use crate::{domains, mir_sha3_256, MirCiphertext, MirError, MirPubkey, CIPHERTEXT_BYTES, PUBKEY_BYTES};
+
+const ALPHABET: &[u8; 32] = b"ybndrfg8ejkmcpqxot1uwisza345h769";
+
+/// Characters needed for `bytes` serialized bytes plus the check byte.
+const fn encoded_len(bytes: usize) -> usize {
+    ((bytes + 1) * 8).div_ceil(5)
+}
+
+/// Largest payload (serialized bytes plus check byte) any decoder accepts.
+const MAX_PAYLOAD: usize = 1 + if PUBKEY_BYTES > CIPHERTEXT_BYTES { PUBKEY_BYTES } else { CIPHERTEXT_BYTES };
+
+/* === [1. Codec] === */
+
+fn checksum(bytes: &[u8]) -> u8 {
+    let mut digest = [0u8; 32];
+    mir_sha3_256(&mut digest, &[bytes], domains::ZBASE32_CHECK);
+    digest[0]
+}
+
+/// Writes `bytes || checksum(bytes)` into `out`, which must be exactly
+/// `encoded_len(bytes.len())` long.
+fn encode<'a>(bytes: &[u8], out: &'a mut [u8]) -> &'a str {
+    let written = pack(bytes.iter().copied().chain([checksum(bytes)]), out);
+    debug_assert_eq!(written, out.len());
+    core::str::from_utf8(out).expect("z-base-32 is ASCII")
+}
+
+/// Packs `data` into `out` and returns the number of characters written.
+fn pack(data: impl Iterator<Item = u8>, out: &mut [u8]) -> usize {
+    let mut written = 0;
+
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for byte in data {
+        acc = (acc << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out[written] = ALPHABET[((acc >> bits) & 0x1F) as usize];
+            written += 1;
+        }
+    }
+    if bits > 0 {
+        out[written] = ALPHABET[((acc << (5 - bits)) & 0x1F) as usize];
+        written += 1;
+    }
+    written
+}
+
+fn symbol(c: u8) -> Result<u32, MirError> {
+    let c = c.to_ascii_lowercase();
+    match ALPHABET.iter().position(|&a| a == c) {
+        Some(v) => Ok(v as u32),
+        None => Err(MirError::Malformed),
+    }
+}
+
+/// Decodes `text` into `buf` and verifies its checksum, returning the
+/// payload without it.
+fn decode<'a>(text: &str, buf: &'a mut [u8; MAX_PAYLOAD]) -> Result<&'a [u8], MirError> {
+    let len = unpack(text, buf)?;
+    let (check, payload) = match buf[..len].split_last() {
+        Some((&check, payload)) => (check, payload),
+        None => return Err(MirError::BadLength),
+    };
+    if checksum(payload) != check {
+        return Err(MirError::BadChecksum);
+    }
+    Ok(payload)
+}
+
+/// Unpacks `text` into `out` and returns the number of bytes written.
+fn unpack(text: &str, out: &mut [u8]) -> Result<usize, MirError> {
+    let mut written = 0;
+
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for &c in text.as_bytes() {
+        acc = (acc << 5) | symbol(c)?;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            *out.get_mut(written).ok_or(MirError::BadLength)? = (acc >> bits) as u8;
+            written += 1;
+        }
+    }
+    // Leftover bits are padding: fewer than a character, and all zero.
+    if bits >= 5 || acc & ((1 << bits) - 1) != 0 {
+        return Err(MirError::Malformed);
+    }
+    Ok(written)
+}
+
+/* === [2. Public API] === */
+
+impl MirPubkey {
+    /// Length of `to_zbase32` / `write_zbase32` output in characters.
+    pub const ZBASE32_LEN: usize = encoded_len(PUBKEY_BYTES);
+
+    /// Encodes the public key as checksummed z-base-32.
+    #[cfg(feature = "alloc")]
+    pub fn to_zbase32(&self) -> String {
+        let mut out = [0u8; Self::ZBASE32_LEN];
+        String::from(self.write_zbase32(&mut out))
+    }
+
+    /// `to_zbase32` into a caller-owned buffer, returning the text in it.
+    pub fn write_zbase32<'a>(&self, out: &'a mut [u8; Self::ZBASE32_LEN]) -> &'a str {
+        encode(&self.to_bytes(), out)
+    }
+
+    /// Decodes a public key produced by `to_zbase32`.
+    ///
+    /// # Errors
+    /// `MirError::Malformed` for characters outside the alphabet or
+    /// non-canonical padding, `MirError::BadChecksum` if the checksum does not
+    /// match, and `MirError::BadLength` if the payload is not a public key.
+    pub fn from_zbase32(text: &str) -> Result<Self, MirError> {
+        MirPubkey::from_bytes(decode(text, &mut [0u8; MAX_PAYLOAD])?)
+    }
+}
+
+impl MirCiphertext {
+    /// Length of `to_zbase32` / `write_zbase32` output in characters.
+    pub const ZBASE32_LEN: usize = encoded_len(CIPHERTEXT_BYTES);
+
+    /// Encodes the ciphertext as checksummed z-base-32.
+    #[cfg(feature = "alloc")]
+    pub fn to_zbase32(&self) -> String {
+        let mut out = [0u8; Self::ZBASE32_LEN];
+        String::from(self.write_zbase32(&mut out))
+    }
+
+    /// `to_zbase32` into a caller-owned buffer, returning the text in it.
+    pub fn write_zbase32<'a>(&self, out: &'a mut [u8; Self::ZBASE32_LEN]) -> &'a str {
+        encode(&self.to_bytes(), out)
+    }
+
+    /// Decodes a ciphertext produced by `to_zbase32`.
+    ///
+    /// # Errors
+    /// As `MirPubkey::from_zbase32`.
+    pub fn from_zbase32(text: &str) -> Result<Self, MirError> {
+        MirCiphertext::from_bytes(decode(text, &mut [0u8; MAX_PAYLOAD])?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decaps, encaps, encaps_deterministic, keygen, keygen_from_seed};
+
+    fn packed(data: &[u8]) -> ([u8; 8], usize) {
+        let mut out = [0u8; 8];
+        let len = pack(data.iter().copied(), &mut out);
+        (out, len)
+    }
+
+    #[test]
+    fn test_zbase32_roundtrip() {
+        let (pk, vault) = keygen();
+        let mut buf = [0u8; MirPubkey::ZBASE32_LEN];
+        let text = pk.write_zbase32(&mut buf);
+        assert!(text.bytes().all(|c| ALPHABET.contains(&c)));
+        let pk2 = MirPubkey::from_zbase32(text).unwrap();
+        assert_eq!(pk2.to_bytes(), pk.to_bytes());
+
+        let (ct, key) = encaps(&pk);
+        let mut buf = [0u8; MirCiphertext::ZBASE32_LEN];
+        let ct2 = MirCiphertext::from_zbase32(ct.write_zbase32(&mut buf)).unwrap();
+        assert_eq!(decaps(&ct2, &vault).key, key.key);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_zbase32_string() {
+        let (pk, _) = keygen();
+        let text = pk.to_zbase32();
+        assert_eq!(text.len(), MirPubkey::ZBASE32_LEN);
+        assert_eq!(text, pk.write_zbase32(&mut [0u8; MirPubkey::ZBASE32_LEN]));
+        assert!(MirPubkey::from_zbase32(&text.to_ascii_uppercase()).is_ok());
+    }
+
+    #[test]
+    fn test_zbase32_packing() {
+        let cases: [(&[u8], &[u8]); 5] = [
+            (&[], b""),
+            (&[0x00], b"yy"),
+            (&[0xFF], b"9h"),
+            (&[0xF0], b"6y"),
+            (&[0xF0, 0x0F], b"6y8o"),
+        ];
+        for (data, text) in cases {
+            let (out, len) = packed(data);
+            assert_eq!(&out[..len], text);
+        }
+        let mut out = [0u8; 4];
+        assert_eq!(unpack("6y8o", &mut out), Ok(2));
+        assert_eq!(out[..2], [0xF0, 0x0F]);
+
+        // Non-zero padding bits and dangling characters are not canonical
+        assert_eq!(unpack("6n", &mut out).err(), Some(MirError::Malformed));
+        assert_eq!(unpack("6yy", &mut out).err(), Some(MirError::Malformed));
+        // Nothing longer than a buffer's worth is read
+        assert_eq!(unpack("6y8o6y8o6y", &mut out).err(), Some(MirError::BadLength));
+    }
+
+    #[test]
+    fn test_zbase32_rejects_corruption() {
+        // Fixed inputs: a one-byte checksum misses 1 in 256 random typos
+        let (pk, _) = keygen_from_seed(&[0x21u8; 32]).unwrap();
+        let (ct, _) = encaps_deterministic(&pk, &[0x22u8; 32]);
+        let mut text = [0u8; MirCiphertext::ZBASE32_LEN];
+        ct.write_zbase32(&mut text);
+
+        // One mistyped character
+        let mut bytes = text;
+        bytes[10] = if bytes[10] == b'y' { b'b' } else { b'y' };
+        let typo = core::str::from_utf8(&bytes).unwrap();
+        assert_eq!(MirCiphertext::from_zbase32(typo).err(), Some(MirError::BadChecksum));
+
+        // Look-alike characters are not in the alphabet
+        let mut bytes = text;
+        bytes[0] = b'0';
+        let bad = core::str::from_utf8(&bytes).unwrap();
+        assert_eq!(MirCiphertext::from_zbase32(bad).err(), Some(MirError::Malformed));
+
+        // A valid code for the wrong type
+        let mut pk_text = [0u8; MirPubkey::ZBASE32_LEN];
+        assert_eq!(MirCiphertext::from_zbase32(pk.write_zbase32(&mut pk_text)).err(), Some(MirError::BadLength));
+        assert_eq!(MirCiphertext::from_zbase32("").err(), Some(MirError::BadLength));
+    }
+}
@@ -0,0 +1,78 @@
+//! dudect-style timing regression test for `decaps` (`ct-test` feature).
+//!
+//! Kept in its own test binary so no other test competes for the CPU while
+//! it measures; run it with `cargo test --features ct-test --release`.
+
+#![cfg(feature = "ct-test")]
+
+use std::time::Instant;
+
+use mirletis::{decaps, encaps_deterministic, keygen_from_seed, MirCiphertext};
+
+const SAMPLES: usize = 20_000;
+
+/// Ciphertexts per class, cycled through while timing.
+const POOL: usize = 1024;
+
+/// Fixed xorshift stream for the class schedule and the random `u` vectors.
+struct TestRng(u64);
+
+impl TestRng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let word = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&word[..chunk.len()]);
+        }
+    }
+}
+
+/// Class A repeats one ciphertext, class B uses random `u` vectors; both
+/// share the same public mask, because the KDF legitimately does more work
+/// for a larger `cnt`. Both pools are built before timing starts, so each
+/// decaps reads its input the same way whatever the class. The classes are
+/// interleaved at random, the slowest 10% of each is cropped as scheduler
+/// noise, and Welch's t-statistic must stay below 10 (dudect's threshold for
+/// a definite leak). This catches a gross data-dependent branch on `u * s`,
+/// not subtle leaks.
+#[test]
+fn test_decaps_timing_welch() {
+    let (pk, vault) = keygen_from_seed(&[0x17u8; 32]).unwrap();
+    let (fixed, _) = encaps_deterministic(&pk, &[0x18u8; 32]);
+    let mut rng = TestRng(0x9E37_79B9_7F4A_7C15);
+
+    let mut pools: [Vec<MirCiphertext>; 2] = [vec![fixed; POOL], vec![fixed; POOL]];
+    for ct in pools[1].iter_mut() {
+        rng.fill_bytes(&mut ct.u);
+    }
+    let schedule: Vec<usize> = (0..2 * SAMPLES).map(|_| (rng.next_u64() & 1) as usize).collect();
+
+    let mut times = [Vec::with_capacity(2 * SAMPLES), Vec::with_capacity(2 * SAMPLES)];
+    let mut next = [0usize; 2];
+    for &class in &schedule {
+        let ct = &pools[class][next[class] % POOL];
+        next[class] += 1;
+        let start = Instant::now();
+        std::hint::black_box(decaps(std::hint::black_box(ct), &vault));
+        times[class].push(start.elapsed().as_nanos() as f64);
+    }
+
+    let stats = |t: &mut Vec<f64>| {
+        t.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        t.truncate(t.len() * 9 / 10);
+        let n = t.len() as f64;
+        let mean = t.iter().sum::<f64>() / n;
+        let var = t.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / (n - 1.0);
+        (mean, var, n)
+    };
+    let (m0, v0, n0) = stats(&mut times[0]);
+    let (m1, v1, n1) = stats(&mut times[1]);
+    let t = (m0 - m1) / (v0 / n0 + v1 / n1).sqrt();
+    assert!(t.abs() < 10.0, "timing leak: t = {:.2} (means {:.0} ns vs {:.0} ns)", t, m0, m1);
+}
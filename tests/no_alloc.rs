@@ -0,0 +1,73 @@
+//! Checks that the allocator-free entry points really do not allocate.
+//!
+//! Kept in its own test binary: the counting allocator is global, and
+//! the lib tests running alongside would allocate on other threads.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use mirletis::{decaps, encaps_into, keygen_from_seed, MirCiphertext, K, N};
+use rand::{CryptoRng, RngCore};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Fixed xorshift stream; good enough to drive encapsulation in a test.
+struct TestRng(u64);
+
+impl RngCore for TestRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let word = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&word[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for TestRng {}
+
+#[test]
+fn test_encaps_into_does_not_allocate() {
+    let (pk, vault) = keygen_from_seed(&[0x31u8; 32]).unwrap();
+    let mut rng = TestRng(0x9E37_79B9_7F4A_7C15);
+    let mut scratch = [0i16; K * N];
+    let mut ct = MirCiphertext::default();
+
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    let key = encaps_into(&pk, &mut rng, &mut scratch, &mut ct).unwrap();
+    let after = ALLOCATIONS.load(Ordering::SeqCst);
+    assert_eq!(after - before, 0, "encaps_into allocated");
+
+    assert_eq!(decaps(&ct, &vault).key, key.key);
+}